@@ -0,0 +1,245 @@
+use std::fs::{metadata, read_dir, symlink_metadata, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use zip::write::FileOptions;
+
+use crate::tree;
+
+// Archives whose members are kept so listing them again doesn't decompress them
+const CACHED_ARCHIVES: usize = 8;
+
+// Every member of an archive as (path, is_dir), in the order they are stored
+type Members = Vec<(String, bool)>;
+
+// (archive path, modified time, members), the most recently listed last
+static MEMBERS: Mutex<Vec<(String, SystemTime, Members)>> = Mutex::new(Vec::new());
+
+#[derive(Clone, Copy)]
+pub enum ArchiveFormat {
+    Tar,
+    TarGz,
+    Zip,
+}
+
+impl ArchiveFormat {
+    pub fn next(&self) -> ArchiveFormat {
+        match self {
+            ArchiveFormat::TarGz => ArchiveFormat::Zip,
+            ArchiveFormat::Zip => ArchiveFormat::Tar,
+            ArchiveFormat::Tar => ArchiveFormat::TarGz,
+        }
+    }
+
+    pub fn extension(&self) -> &str {
+        match self {
+            ArchiveFormat::Tar => "tar",
+            ArchiveFormat::TarGz => "tar.gz",
+            ArchiveFormat::Zip => "zip",
+        }
+    }
+}
+
+pub fn is_archive(name: &str) -> bool {
+    let name = name.to_lowercase();
+    name.ends_with(".tar") || name.ends_with(".tar.gz") || name.ends_with(".tgz") || name.ends_with(".zip")
+}
+
+// Splits a path that goes through an archive into the archive on disk and the path
+// inside it. `resolve` turns a listed path into a path on disk.
+pub fn split(path: &str, resolve: impl Fn(&str) -> Option<String>) -> Option<(String, String)> {
+    for ancestor in Path::new(path).ancestors() {
+        let is_archive_name = ancestor.file_name().and_then(|name| name.to_str()).map(is_archive).unwrap_or(false);
+        if !is_archive_name {
+            continue;
+        }
+
+        let archive_path = ancestor.display().to_string();
+        if let Some(real_path) = resolve(&archive_path) {
+            if Path::new(&real_path).is_file() {
+                let inner = path[archive_path.len()..].trim_matches('/').to_string();
+                return Some((real_path, inner));
+            }
+        }
+    }
+
+    None
+}
+
+// The members are read again once the archive changes
+fn members(archive: &str) -> io::Result<Members> {
+    let modified = metadata(archive)?.modified()?;
+    let mut cache = MEMBERS.lock().unwrap_or_else(|err| err.into_inner());
+
+    if let Some(i) = cache.iter().position(|(path, _, _)| path == archive) {
+        let cached = cache.remove(i);
+        if cached.1 == modified {
+            let members = cached.2.clone();
+            cache.push(cached);
+            return Ok(members);
+        }
+    }
+
+    let members = read_members(archive)?;
+    cache.push((archive.to_string(), modified, members.clone()));
+    if cache.len() > CACHED_ARCHIVES {
+        cache.remove(0);
+    }
+
+    Ok(members)
+}
+
+fn read_members(archive: &str) -> io::Result<Members> {
+    fn tar_members(reader: impl Read) -> io::Result<Members> {
+        let mut members: Members = vec![];
+
+        for entry in tar::Archive::new(reader).entries()? {
+            let entry = entry?;
+            let is_dir = entry.header().entry_type().is_dir();
+            members.push((entry.path()?.display().to_string(), is_dir));
+        }
+
+        Ok(members)
+    }
+
+    let file = File::open(archive)?;
+    let name = archive.to_lowercase();
+
+    if name.ends_with(".zip") {
+        let mut zip = zip::ZipArchive::new(file).map_err(io::Error::from)?;
+        let mut members: Members = vec![];
+
+        for i in 0..zip.len() {
+            let member = zip.by_index(i).map_err(io::Error::from)?;
+            members.push((member.name().to_string(), member.is_dir()));
+        }
+
+        Ok(members)
+    } else if name.ends_with(".tar") {
+        tar_members(file)
+    } else {
+        tar_members(GzDecoder::new(file))
+    }
+}
+
+// Lists the members directly inside `inner` as entries below `path`. Directories
+// that only show up in the paths of their members are listed too.
+pub fn list(archive: &str, inner: &str, path: &str) -> io::Result<Vec<tree::Entry>> {
+    let prefix = if inner.is_empty() { String::new() } else { format!("{}/", inner) };
+    let mut entries: Vec<tree::Entry> = vec![];
+
+    for (member, is_dir) in members(archive)? {
+        let member = member.trim_start_matches("./").trim_matches('/');
+
+        let rest = match member.strip_prefix(&prefix) {
+            Some(rest) if !rest.is_empty() => rest,
+            _ => continue,
+        };
+
+        let (name, is_dir) = match rest.split_once('/') {
+            Some((name, _)) => (name, true),
+            None => (rest, is_dir),
+        };
+
+        if !entries.iter().any(|e| e.name == name) {
+            entries.push(tree::Entry {
+                name: name.to_string(),
+                path: format!("{}/{}", path, name),
+                r#type: String::from(if is_dir { "d" } else { "f" }),
+            });
+        }
+    }
+
+    Ok(entries)
+}
+
+// Everything below `path` as (name in the archive, path on disk, is_dir, size), parents first
+fn walk(name: &str, path: &Path, items: &mut Vec<(String, PathBuf, bool, u64)>) -> io::Result<()> {
+    let is_dir = path.is_dir();
+    let size = if is_dir { 0 } else { path.metadata()?.len() };
+    items.push((name.to_string(), path.to_path_buf(), is_dir, size));
+
+    // Linked directories are stored but not walked, they could loop
+    let is_symlink = symlink_metadata(path)?.file_type().is_symlink();
+    if is_dir && !is_symlink {
+        let mut children: Vec<PathBuf> = read_dir(path)?.map(|e| e.map(|e| e.path())).collect::<io::Result<_>>()?;
+        children.sort();
+
+        for child in children {
+            let child_name = child.file_name().unwrap().to_string_lossy().to_string();
+            walk(&format!("{}/{}", name, child_name), &child, items)?;
+        }
+    }
+
+    Ok(())
+}
+
+// Packs `sources`, given as (name in the archive, path on disk), into a new archive
+// at `dest`. `progress` is called after every walked entry with the bytes packed so far
+// and the bytes of all files together.
+pub fn create(dest: &Path, format: ArchiveFormat, sources: &[(String, PathBuf)], mut progress: impl FnMut(u64, u64)) -> io::Result<()> {
+    let mut items: Vec<(String, PathBuf, bool, u64)> = vec![];
+    for (name, path) in sources {
+        walk(name, path, &mut items)?;
+    }
+
+    let total: u64 = items.iter().map(|(_, _, _, size)| size).sum();
+    let mut done: u64 = 0;
+
+    let file = File::create(dest)?;
+
+    match format {
+        ArchiveFormat::Zip => {
+            let mut zip = zip::ZipWriter::new(file);
+            // Files over 4 GiB need the zip64 fields
+            let options = FileOptions::default().compression_method(zip::CompressionMethod::Deflated).large_file(true);
+
+            for (name, path, is_dir, size) in items.iter() {
+                if *is_dir {
+                    zip.add_directory(name.as_str(), options).map_err(io::Error::from)?;
+                } else {
+                    zip.start_file(name.as_str(), options).map_err(io::Error::from)?;
+                    io::copy(&mut File::open(path)?, &mut zip)?;
+                }
+                done += size;
+                progress(done, total);
+            }
+
+            zip.finish().map_err(io::Error::from)?;
+        },
+        ArchiveFormat::Tar | ArchiveFormat::TarGz => {
+            fn append<W: io::Write>(builder: &mut tar::Builder<W>, items: &[(String, PathBuf, bool, u64)], mut progress: impl FnMut(u64)) -> io::Result<()> {
+                for (name, path, is_dir, size) in items.iter() {
+                    if *is_dir {
+                        builder.append_dir(name, path)?;
+                    } else {
+                        builder.append_path_with_name(path, name)?;
+                    }
+                    progress(*size);
+                }
+                Ok(())
+            }
+
+            let mut packed = |size: u64| {
+                done += size;
+                progress(done, total);
+            };
+
+            if let ArchiveFormat::TarGz = format {
+                let mut builder = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+                append(&mut builder, &items, &mut packed)?;
+                builder.into_inner()?.finish()?;
+            } else {
+                let mut builder = tar::Builder::new(file);
+                append(&mut builder, &items, &mut packed)?;
+                builder.into_inner()?;
+            }
+        },
+    }
+
+    Ok(())
+}
@@ -0,0 +1,105 @@
+use crate::complete;
+
+const USAGE: &str = "Usage: rust-command-line-app [OPTIONS] [START_PATH]
+
+Interactive terminal file explorer.
+
+Arguments:
+  [START_PATH]     Directory to open, defaults to start_path from the config or /
+
+Options:
+  --tree <FILE>    tree.json to browse, defaults to tree from the config or ./tree.json
+  --read-only      Don't change any files or the tree
+  --real           Browse the real filesystem instead of the tree
+  --no-restore     Start fresh instead of where the last session was left
+  -h, --help       Print this help
+  -V, --version    Print the version";
+
+#[derive(Default)]
+pub struct Args {
+    pub tree: Option<String>,
+    pub start_path: Option<String>,
+    pub read_only: bool,
+    pub real: bool,
+    pub no_restore: bool,
+    pub help: bool,
+    pub version: bool,
+}
+
+pub fn usage() -> &'static str {
+    USAGE
+}
+
+pub fn version() -> String {
+    format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"))
+}
+
+// A relative start path is taken from `cwd`
+pub fn parse_args(mut args: impl Iterator<Item = String>, cwd: &str) -> Result<Args, String> {
+    let mut parsed = Args::default();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => parsed.help = true,
+            "-V" | "--version" => parsed.version = true,
+            "--read-only" => parsed.read_only = true,
+            "--real" => parsed.real = true,
+            "--no-restore" => parsed.no_restore = true,
+            "--tree" => match args.next() {
+                Some(tree) if !tree.starts_with('-') => parsed.tree = Some(tree),
+                _ => return Err(String::from("--tree needs a file, e.g. --tree ./tree.json")),
+            },
+            _ => {
+                if let Some(tree) = arg.strip_prefix("--tree=") {
+                    parsed.tree = Some(tree.to_string());
+                } else if arg.starts_with('-') {
+                    return Err(format!("Unknown option '{}'", arg));
+                } else if parsed.start_path.is_some() {
+                    return Err(format!("Unexpected argument '{}', only one start path can be given", arg));
+                } else {
+                    parsed.start_path = Some(complete::absolute(&arg, cwd));
+                }
+            },
+        }
+    }
+
+    Ok(parsed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args, String> {
+        parse_args(args.iter().map(|arg| arg.to_string()), "/home/mats")
+    }
+
+    #[test]
+    fn resolves_relative_start_paths() {
+        assert_eq!(parse(&["docs"]).unwrap().start_path.as_deref(), Some("/home/mats/docs"));
+        assert_eq!(parse(&["../x/./y"]).unwrap().start_path.as_deref(), Some("/home/x/y"));
+        assert_eq!(parse(&["."]).unwrap().start_path.as_deref(), Some("/home/mats"));
+    }
+
+    #[test]
+    fn strips_trailing_slashes() {
+        assert_eq!(parse(&["/tmp/"]).unwrap().start_path.as_deref(), Some("/tmp"));
+        assert_eq!(parse(&["/"]).unwrap().start_path.as_deref(), Some("/"));
+    }
+
+    #[test]
+    fn reads_options() {
+        let args = parse(&["--read-only", "--tree", "t.json", "--real"]).unwrap();
+        assert!(args.read_only && args.real && !args.no_restore);
+        assert_eq!(args.tree.as_deref(), Some("t.json"));
+        assert_eq!(parse(&["--tree=x.json"]).unwrap().tree.as_deref(), Some("x.json"));
+    }
+
+    #[test]
+    fn rejects_bad_arguments() {
+        assert!(parse(&["--tree"]).is_err());
+        assert!(parse(&["--tree", "--real"]).is_err());
+        assert!(parse(&["--nope"]).is_err());
+        assert!(parse(&["/a", "/b"]).is_err());
+    }
+}
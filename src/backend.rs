@@ -0,0 +1,63 @@
+use std::fs::Metadata;
+use std::io;
+use std::path::PathBuf;
+
+use crate::archive;
+use crate::find;
+use crate::journal;
+use crate::reconcile;
+use crate::trash;
+use crate::tree;
+
+// Where listings come from and where changes go. Paths are the ones shown in the
+// explorer, `resolve` turns them into paths on disk. The journal is the one thing
+// that changes the disk without it, undo and redo replay ops on paths that are
+// already real and must not be recorded again.
+pub trait Backend {
+    // The entries directly inside `path`, archives are listed like directories
+    fn list(&self, path: &str) -> Result<Vec<tree::Entry>, String>;
+    // Metadata of the file `path` points at, links are followed
+    fn stat(&self, path: &str) -> io::Result<Metadata>;
+    fn resolve(&self, path: &str) -> Result<String, tree::ResolveError>;
+    // What the link at `path` points to, None when it isn't a link
+    fn link_target(&self, path: &str) -> Option<String>;
+    fn exists(&self, path: &str) -> bool;
+
+    fn create_file(&mut self, path: &str) -> Result<tree::Entry, String>;
+    fn create_dir(&mut self, path: &str) -> Result<tree::Entry, String>;
+    fn link(&mut self, target: &str, path: &str) -> Result<(), String>;
+    fn rename(&mut self, from: &str, to: &str) -> Result<(), String>;
+    fn copy(&mut self, from: &str, to: &str) -> Result<(), String>;
+    // Moves `path` and everything below it to the trash
+    fn trash(&mut self, path: &str) -> Result<(), String>;
+    // Moves `path` out of the way to `staged`, the journal removes it for good once it is pruned
+    fn delete(&mut self, path: &str, staged: &str) -> Result<(), String>;
+    // Brings back what the trash keeps as `trash_name` to `path`
+    fn restore(&mut self, path: &str, trash_name: &str) -> Result<(), String>;
+    fn purge(&mut self, trash_name: &str) -> Result<(), String>;
+    fn trash_items(&self) -> Result<Vec<trash::TrashItem>, String>;
+    // Packs `sources`, real paths under their names in the archive, into a new archive
+    // at `path`. Nothing is left behind when it fails.
+    fn compress(&mut self, path: &str, format: archive::ArchiveFormat, sources: &[(String, PathBuf)], progress: &mut dyn FnMut(u64, u64)) -> Result<(), String>;
+    // What the changes since the last call did on disk, for the journal to undo
+    fn take_ops(&mut self) -> Vec<journal::DiskOp>;
+    fn save(&self) -> Result<(), String>;
+
+    // The tree behind the listings, None when there is only the disk
+    fn tree(&mut self) -> Option<&mut dyn TreeStore> {
+        None
+    }
+}
+
+// What only a backend that keeps a tree can do
+pub trait TreeStore {
+    fn find(&self, query: &str, mode: &find::FindMode) -> Result<Vec<tree::Entry>, String>;
+    fn sync(&self, dir: &str) -> Result<Vec<reconcile::Diff>, String>;
+    fn apply_diff(&mut self, diff: reconcile::Diff);
+    // Reads the tree from its file again
+    fn reload(&mut self) -> Result<(), String>;
+
+    // What the changes since the last call did to the tree, for the journal to undo
+    fn take_tree_ops(&mut self) -> Vec<journal::TreeOp>;
+    fn apply(&mut self, op: &journal::TreeOp);
+}
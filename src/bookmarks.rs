@@ -0,0 +1,120 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+
+use crate::json_file;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Bookmark {
+    pub name: String,
+    pub path: String,
+}
+
+// Marks set with m<letter> and named bookmarks
+#[derive(Serialize, Deserialize, Default)]
+pub struct Bookmarks {
+    #[serde(default)]
+    pub marks: BTreeMap<String, String>,
+    #[serde(default)]
+    pub named: Vec<Bookmark>,
+    #[serde(skip)]
+    path: PathBuf,
+}
+
+impl Bookmarks {
+    // No marks or bookmarks yet, saved to `path`
+    pub fn empty(path: PathBuf) -> Bookmarks {
+        Bookmarks { marks: BTreeMap::new(), named: vec![], path }
+    }
+
+    pub fn load(path: PathBuf) -> Result<Bookmarks, String> {
+        match json_file::load::<Bookmarks>(&path)? {
+            Some(bookmarks) => Ok(Bookmarks { path, ..bookmarks }),
+            None => Ok(Bookmarks::empty(path)),
+        }
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        json_file::save(&self.path, self)
+    }
+
+    pub fn set_mark(&mut self, mark: char, path: &str) {
+        self.marks.insert(mark.to_string(), path.to_string());
+    }
+
+    pub fn mark(&self, mark: char) -> Option<&String> {
+        self.marks.get(&mark.to_string())
+    }
+
+    pub fn add(&mut self, name: &str, path: &str) {
+        self.named.retain(|b| b.name != name);
+        self.named.push(Bookmark { name: name.to_string(), path: path.to_string() });
+    }
+
+    // Marks followed by the named bookmarks, as they are listed in the popup
+    pub fn list(&self) -> Vec<Bookmark> {
+        let marks = self.marks.iter().map(|(mark, path)| Bookmark { name: format!("'{}", mark), path: path.clone() });
+        marks.chain(self.named.iter().cloned()).collect()
+    }
+
+    pub fn remove(&mut self, i: usize) {
+        if i < self.marks.len() {
+            let mark = self.marks.keys().nth(i).cloned().unwrap();
+            self.marks.remove(&mark);
+        } else if i - self.marks.len() < self.named.len() {
+            self.named.remove(i - self.marks.len());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bookmarks() -> Bookmarks {
+        let mut bookmarks = Bookmarks::empty(PathBuf::new());
+        bookmarks.set_mark('b', "/b");
+        bookmarks.set_mark('a', "/a");
+        bookmarks.add("docs", "/docs");
+        bookmarks.add("src", "/src");
+        bookmarks
+    }
+
+    fn names(bookmarks: &Bookmarks) -> Vec<String> {
+        bookmarks.list().into_iter().map(|b| b.name).collect()
+    }
+
+    #[test]
+    fn lists_marks_before_named_bookmarks() {
+        assert_eq!(names(&bookmarks()), vec!["'a", "'b", "docs", "src"]);
+    }
+
+    #[test]
+    fn removes_marks_and_named_bookmarks_by_position() {
+        let mut bookmarks = bookmarks();
+
+        bookmarks.remove(1);
+        assert_eq!(names(&bookmarks), vec!["'a", "docs", "src"]);
+        assert_eq!(bookmarks.mark('b'), None);
+
+        bookmarks.remove(2);
+        assert_eq!(names(&bookmarks), vec!["'a", "docs"]);
+    }
+
+    #[test]
+    fn ignores_positions_past_the_end() {
+        let mut bookmarks = bookmarks();
+        bookmarks.remove(4);
+
+        assert_eq!(bookmarks.list().len(), 4);
+    }
+
+    #[test]
+    fn replaces_a_bookmark_with_the_same_name() {
+        let mut bookmarks = bookmarks();
+        bookmarks.add("docs", "/other");
+
+        assert_eq!(names(&bookmarks), vec!["'a", "'b", "src", "docs"]);
+        assert_eq!(bookmarks.list()[3].path, "/other");
+    }
+}
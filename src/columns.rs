@@ -0,0 +1,210 @@
+use std::collections::HashMap;
+use std::fs::{self, Metadata};
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use serde::{Deserialize, Serialize};
+
+use crate::backend::Backend;
+use crate::date;
+use crate::tree;
+
+// Which metadata columns are shown next to the names
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Columns {
+    pub size: bool,
+    pub modified: bool,
+    pub permissions: bool,
+    pub owner: bool,
+    pub target: bool,
+}
+
+impl Columns {
+    pub fn new() -> Columns {
+        Columns {
+            size: false,
+            modified: false,
+            permissions: false,
+            owner: false,
+            target: false,
+        }
+    }
+
+    pub fn any(&self) -> bool {
+        self.size || self.modified || self.permissions || self.owner || self.target
+    }
+
+    pub fn toggle(&mut self, c: char) {
+        match c {
+            's' => self.size = !self.size,
+            't' => self.modified = !self.modified,
+            'p' => self.permissions = !self.permissions,
+            'o' => self.owner = !self.owner,
+            'l' => self.target = !self.target,
+            _ => {}
+        }
+    }
+
+    pub fn label(&self) -> String {
+        let flag = |on: bool, name: &str| if on { name.to_uppercase() } else { name.to_string() };
+
+        format!("Columns: {} {} {} {} {} (uppercase shown, ESC close)",
+            flag(self.size, "[s]ize"), flag(self.modified, "[t]ime"), flag(self.permissions, "[p]erms"),
+            flag(self.owner, "[o]wner"), flag(self.target, "[l]ink"))
+    }
+}
+
+pub struct EntryInfo {
+    size: String,
+    modified: String,
+    permissions: String,
+    owner: String,
+    target: String,
+}
+
+// Metadata of the listed entries, keyed by directory so it is only read once per listing
+pub struct MetadataCache {
+    dirs: HashMap<String, HashMap<String, EntryInfo>>,
+    users: HashMap<u32, String>,
+    groups: HashMap<u32, String>,
+}
+
+impl MetadataCache {
+    pub fn new() -> MetadataCache {
+        MetadataCache {
+            dirs: HashMap::new(),
+            users: read_names("/etc/passwd"),
+            groups: read_names("/etc/group"),
+        }
+    }
+
+    pub fn invalidate(&mut self, dir: &str) {
+        self.dirs.remove(dir);
+    }
+
+    pub fn clear(&mut self) {
+        self.dirs.clear();
+    }
+
+    pub fn info(&mut self, dir: &str, entry: &tree::Entry, backend: &dyn Backend) -> &EntryInfo {
+        let users = &self.users;
+        let groups = &self.groups;
+
+        self.dirs.entry(dir.to_string()).or_default().entry(entry.path.clone()).or_insert_with(|| {
+            // Links are read through their target
+            let target = backend.link_target(&entry.path).unwrap_or_default();
+
+            match backend.stat(&entry.path) {
+                Ok(meta) => EntryInfo {
+                    size: if meta.is_dir() { String::from("-") } else { human_size(meta.len()) },
+                    modified: meta.modified().map(format_time).unwrap_or_default(),
+                    permissions: permissions(&meta, !target.is_empty()),
+                    owner: format!("{}:{}",
+                        users.get(&meta.uid()).cloned().unwrap_or_else(|| meta.uid().to_string()),
+                        groups.get(&meta.gid()).cloned().unwrap_or_else(|| meta.gid().to_string())),
+                    target,
+                },
+                Err(_) => EntryInfo {
+                    size: String::from("?"),
+                    modified: String::from("?"),
+                    permissions: String::from("?"),
+                    owner: String::from("?"),
+                    target,
+                },
+            }
+        })
+    }
+}
+
+// Builds the text shown right of the name
+pub fn format_columns(info: &EntryInfo, columns: &Columns) -> String {
+    let mut parts: Vec<String> = vec![];
+
+    if columns.size {
+        parts.push(format!("{:>7}", info.size));
+    }
+    if columns.modified {
+        parts.push(format!("{:16}", info.modified));
+    }
+    if columns.permissions {
+        parts.push(format!("{:10}", info.permissions));
+    }
+    if columns.owner {
+        parts.push(format!("{:16}", info.owner));
+    }
+    if columns.target && !info.target.is_empty() {
+        parts.push(format!("-> {}", info.target));
+    }
+
+    parts.join("  ")
+}
+
+// Lays out `label` and `columns` on one row of `width` characters
+pub fn layout_row(label: &str, columns: &str, width: usize) -> String {
+    let label_len = label.chars().count();
+    let columns_len = columns.chars().count();
+
+    if columns.is_empty() || width < columns_len + 12 {
+        return label.chars().take(width).collect();
+    }
+
+    let name_width = width - columns_len - 2;
+    let name: String = if label_len > name_width {
+        label.chars().take(name_width - 1).chain("~".chars()).collect()
+    } else {
+        format!("{}{}", label, " ".repeat(name_width - label_len))
+    };
+
+    format!("{}  {}", name, columns)
+}
+
+pub fn human_size(size: u64) -> String {
+    let units = ["B", "K", "M", "G", "T", "P"];
+    let mut value = size as f64;
+    let mut unit = 0;
+
+    while value >= 1024.0 && unit < units.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{}{}", size, units[0])
+    } else {
+        format!("{:.1}{}", value, units[unit])
+    }
+}
+
+fn format_time(time: std::time::SystemTime) -> String {
+    let (year, month, day, hour, minute, _) = date::local(time);
+
+    format!("{:04}-{:02}-{:02} {:02}:{:02}", year, month, day, hour, minute)
+}
+
+// `meta` is followed through links, so links show the permissions of their target
+fn permissions(meta: &Metadata, is_link: bool) -> String {
+    let mode = meta.permissions().mode();
+    let kind = if is_link { 'l' } else if meta.is_dir() { 'd' } else { '-' };
+    let bits = ['r', 'w', 'x'];
+
+    let mut str = kind.to_string();
+    for i in (0..9).rev() {
+        str.push(if mode & (1 << i) != 0 { bits[(8 - i) % 3] } else { '-' });
+    }
+
+    str
+}
+
+// name:x:id:... lines from /etc/passwd and /etc/group
+fn read_names(file: &str) -> HashMap<u32, String> {
+    let mut names: HashMap<u32, String> = HashMap::new();
+
+    for line in fs::read_to_string(file).unwrap_or_default().lines() {
+        let fields: Vec<&str> = line.split(':').collect();
+        if fields.len() > 2 {
+            if let Ok(id) = fields[2].parse::<u32>() {
+                names.insert(id, fields[0].to_string());
+            }
+        }
+    }
+
+    names
+}
@@ -0,0 +1,90 @@
+use crate::backend::Backend;
+use crate::tree;
+
+// Turns a path typed relative to `curr_path` into an absolute one without . and ..
+pub fn absolute(input: &str, curr_path: &str) -> String {
+    let joined = if input.starts_with('/') { input.to_string() } else { format!("{}/{}", curr_path, input) };
+    let mut parts: Vec<&str> = vec![];
+
+    for part in joined.split('/') {
+        match part {
+            "" | "." => {},
+            ".." => { parts.pop(); },
+            _ => parts.push(part),
+        }
+    }
+
+    format!("/{}", parts.join("/"))
+}
+
+// Completes the last part of `input` from the entries of the folder it points into,
+// links included. Returns the new input and the names that are left when it is ambiguous.
+pub fn complete(input: &str, curr_path: &str, backend: &dyn Backend) -> (String, Vec<String>) {
+    let (dir_part, partial) = match input.rfind('/') {
+        Some(i) => (&input[..=i], &input[i + 1..]),
+        None => ("", input),
+    };
+
+    let entries = backend.list(&absolute(dir_part, curr_path)).unwrap_or_default();
+    let mut matches: Vec<&tree::Entry> = entries.iter().filter(|e| e.name.starts_with(partial)).collect();
+    matches.sort_by(|a, b| a.name.cmp(&b.name));
+
+    match matches.len() {
+        0 => (input.to_string(), vec![]),
+        1 => {
+            let suffix = if matches[0].r#type == "d" || matches[0].r#type == "dl" { "/" } else { "" };
+            (format!("{}{}{}", dir_part, matches[0].name, suffix), vec![])
+        },
+        _ => {
+            let mut prefix = matches[0].name.clone();
+            for e in matches.iter() {
+                while !e.name.starts_with(&prefix) {
+                    prefix.pop();
+                }
+            }
+
+            (format!("{}{}", dir_part, prefix), matches.iter().map(|e| e.name.clone()).collect())
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disk_backend::DiskBackend;
+    use std::fs;
+    use std::os::unix::fs::symlink;
+
+    #[test]
+    fn absolute_resolves_dots() {
+        assert_eq!(absolute("docs", "/home/me"), "/home/me/docs");
+        assert_eq!(absolute("../you/./notes/", "/home/me"), "/home/you/notes");
+        assert_eq!(absolute("/etc/../usr", "/home/me"), "/usr");
+        assert_eq!(absolute("../../..", "/home/me"), "/");
+        assert_eq!(absolute("", "/home/me/"), "/home/me");
+    }
+
+    #[test]
+    fn completes_from_the_folder_the_input_points_into() {
+        let dir = std::env::temp_dir().join(format!("complete-test-{}", std::process::id()));
+        fs::create_dir_all(dir.join("src/bin")).unwrap();
+        fs::write(dir.join("src/main.rs"), "").unwrap();
+        fs::write(dir.join("src/mod.rs"), "").unwrap();
+        fs::write(dir.join("readme"), "").unwrap();
+        symlink(dir.join("src"), dir.join("source")).unwrap();
+
+        let curr_path = dir.display().to_string();
+        let backend = DiskBackend::default();
+        let complete = |input: &str| complete(input, &curr_path, &backend);
+
+        assert_eq!(complete("re"), (String::from("readme"), vec![]));
+        assert_eq!(complete("src/b"), (String::from("src/bin/"), vec![]));
+        assert_eq!(complete("sourc"), (String::from("source/"), vec![]));
+        assert_eq!(complete("s"), (String::from("s"), vec![String::from("source"), String::from("src")]));
+        assert_eq!(complete("src/m"), (String::from("src/m"), vec![String::from("main.rs"), String::from("mod.rs")]));
+        assert_eq!(complete("src/x"), (String::from("src/x"), vec![]));
+        assert_eq!(complete(&format!("{}/src/bin/../ma", curr_path)), (format!("{}/src/bin/../main.rs", curr_path), vec![]));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,125 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use serde::Deserialize;
+
+use crate::keys::KeyList;
+
+// Opens files matching `extensions` or `mime` (e.g. "image/*") with `command`.
+// `{}` in the command is replaced by the file, otherwise the file is appended.
+#[derive(Deserialize)]
+pub struct OpenRule {
+    #[serde(default)]
+    pub extensions: Vec<String>,
+    pub mime: Option<String>,
+    pub command: String,
+}
+
+// Color names are black, red, green, yellow, blue, magenta, cyan, white or default
+#[derive(Deserialize, Clone)]
+pub struct ColorPair {
+    pub fg: Option<String>,
+    pub bg: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+pub struct Theme {
+    pub file: Option<ColorPair>,
+    pub dir: Option<ColorPair>,
+    pub link: Option<ColorPair>,
+    pub selection: Option<ColorPair>,
+    pub error: Option<ColorPair>,
+}
+
+#[derive(Deserialize, Default)]
+pub struct Config {
+    pub start_path: Option<String>,
+    pub tree: Option<String>,
+    #[serde(default)]
+    pub keys: HashMap<String, KeyList>,
+    #[serde(default)]
+    pub colors: Theme,
+    #[serde(default)]
+    pub open_with: Vec<OpenRule>,
+}
+
+pub fn parse_color(name: &str) -> Result<i16, String> {
+    match name {
+        "default" => Ok(-1),
+        "black" => Ok(ncurses::COLOR_BLACK),
+        "red" => Ok(ncurses::COLOR_RED),
+        "green" => Ok(ncurses::COLOR_GREEN),
+        "yellow" => Ok(ncurses::COLOR_YELLOW),
+        "blue" => Ok(ncurses::COLOR_BLUE),
+        "magenta" => Ok(ncurses::COLOR_MAGENTA),
+        "cyan" => Ok(ncurses::COLOR_CYAN),
+        "white" => Ok(ncurses::COLOR_WHITE),
+        _ => Err(format!("Unknown color \"{}\"", name)),
+    }
+}
+
+// $XDG_CONFIG_HOME/cli_file_explorer/config.toml
+pub fn config_path() -> PathBuf {
+    let dir = match env::var("XDG_CONFIG_HOME") {
+        Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var("HOME").unwrap_or_default()).join(".config"),
+    };
+
+    dir.join("cli_file_explorer").join("config.toml")
+}
+
+// A missing config file just means the defaults are used
+pub fn parse_config() -> Result<Config, String> {
+    let path = config_path();
+
+    match fs::read_to_string(&path) {
+        Ok(data) => toml::from_str(&data).map_err(|err| format!("{}: {}", path.display(), err)),
+        Err(_) => Ok(Config::default()),
+    }
+}
+
+fn mime_type(path: &Path) -> Option<String> {
+    let output = Command::new("file").args(["--brief", "--mime-type"]).arg(path).output().ok()?;
+
+    if output.status.success() {
+        Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    } else {
+        None
+    }
+}
+
+fn mime_matches(pattern: &str, mime: &str) -> bool {
+    match pattern.strip_suffix("/*") {
+        Some(kind) => mime.split('/').next() == Some(kind),
+        None => pattern == mime,
+    }
+}
+
+impl Config {
+    // Shell command that opens `path`, the file is passed as $1
+    pub fn open_command(&self, path: &Path) -> String {
+        let ext = path.extension().map(|ext| ext.to_string_lossy().to_lowercase()).unwrap_or_default();
+        let mut mime: Option<Option<String>> = None;
+
+        for rule in self.open_with.iter() {
+            let ext_match = rule.extensions.iter().any(|e| e.trim_start_matches('.').to_lowercase() == ext);
+            let mime_match = match &rule.mime {
+                Some(pattern) if !ext_match => mime.get_or_insert_with(|| mime_type(path)).as_deref().map(|m| mime_matches(pattern, m)).unwrap_or(false),
+                _ => false,
+            };
+
+            if ext_match || mime_match {
+                return if rule.command.contains("{}") {
+                    rule.command.replace("{}", "\"$1\"")
+                } else {
+                    format!("{} \"$1\"", rule.command)
+                };
+            }
+        }
+
+        let editor = env::var("VISUAL").or_else(|_| env::var("EDITOR")).unwrap_or_else(|_| String::from("vi"));
+        format!("{} \"$1\"", editor)
+    }
+}
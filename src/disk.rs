@@ -0,0 +1,39 @@
+use std::fs::*;
+use std::io;
+use std::os::unix::fs::symlink;
+use std::path::Path;
+
+// Links are copied as links, following them could loop forever
+pub fn copy_recursive(src: &Path, dest: &Path) -> io::Result<()> {
+    let file_type = symlink_metadata(src)?.file_type();
+
+    if file_type.is_symlink() {
+        symlink(read_link(src)?, dest)?;
+    } else if file_type.is_dir() {
+        create_dir(dest)?;
+        for entry_res in read_dir(src)? {
+            let entry = entry_res?;
+            copy_recursive(&entry.path(), &dest.join(entry.file_name()))?;
+        }
+    } else {
+        copy(src, dest)?;
+    }
+
+    Ok(())
+}
+
+pub fn remove_path(path: &Path) -> io::Result<()> {
+    if symlink_metadata(path)?.is_dir() {
+        remove_dir_all(path)
+    } else {
+        remove_file(path)
+    }
+}
+
+// rename() fails across filesystems, fall back to copying
+pub fn move_path(src: &Path, dest: &Path) -> io::Result<()> {
+    rename(src, dest).or_else(|_| {
+        copy_recursive(src, dest)?;
+        remove_path(src)
+    })
+}
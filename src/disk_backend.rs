@@ -0,0 +1,146 @@
+use std::fs::{self, File, Metadata};
+use std::io;
+use std::os::unix::fs::symlink;
+use std::path::{Path, PathBuf};
+
+use crate::archive;
+use crate::backend::Backend;
+use crate::disk;
+use crate::get_entries::get_entries;
+use crate::journal::DiskOp;
+use crate::trash;
+use crate::tree;
+
+// Lists the real file system, nothing is kept besides what is on disk
+#[derive(Default)]
+pub struct DiskBackend {
+    ops: Vec<DiskOp>,
+}
+
+impl Backend for DiskBackend {
+    fn list(&self, path: &str) -> Result<Vec<tree::Entry>, String> {
+        if let Some((archive_path, inner)) = archive::split(path, |p| Some(p.to_string())) {
+            return archive::list(&archive_path, &inner, path).map_err(|err| err.to_string());
+        }
+
+        let mut entries = get_entries(path).map_err(|err| err.to_string())?;
+
+        // Symlinks get the same types as links in the tree
+        for e in entries.iter_mut() {
+            let is_symlink = fs::symlink_metadata(&e.path).map(|meta| meta.file_type().is_symlink()).unwrap_or(false);
+
+            if is_symlink {
+                e.r#type = if Path::new(&e.path).exists() { format!("{}l", e.r#type) } else { String::from("bl") };
+            }
+        }
+
+        Ok(entries)
+    }
+
+    fn stat(&self, path: &str) -> io::Result<Metadata> {
+        fs::metadata(path)
+    }
+
+    fn resolve(&self, path: &str) -> Result<String, tree::ResolveError> {
+        Ok(path.to_string())
+    }
+
+    fn link_target(&self, path: &str) -> Option<String> {
+        fs::read_link(path).ok().map(|target| target.display().to_string())
+    }
+
+    fn exists(&self, path: &str) -> bool {
+        fs::symlink_metadata(path).is_ok()
+    }
+
+    fn create_file(&mut self, path: &str) -> Result<tree::Entry, String> {
+        if self.exists(path) {
+            return Err(String::from("File already exists"));
+        }
+
+        File::create(path).map_err(|err| err.to_string())?;
+        self.ops.push(DiskOp::Create { path: path.to_string() });
+        Ok(tree::Entry { name: tree::file_name(path), path: path.to_string(), r#type: String::from("f") })
+    }
+
+    fn create_dir(&mut self, path: &str) -> Result<tree::Entry, String> {
+        fs::create_dir(path).map_err(|err| err.to_string())?;
+        self.ops.push(DiskOp::Create { path: path.to_string() });
+        Ok(tree::Entry { name: tree::file_name(path), path: path.to_string(), r#type: String::from("d") })
+    }
+
+    fn link(&mut self, target: &str, path: &str) -> Result<(), String> {
+        if !Path::new(target).exists() {
+            return Err(format!("{} not found", target));
+        }
+
+        symlink(target, path).map_err(|err| err.to_string())?;
+        self.ops.push(DiskOp::Create { path: path.to_string() });
+        Ok(())
+    }
+
+    fn rename(&mut self, from: &str, to: &str) -> Result<(), String> {
+        if self.exists(to) {
+            return Err(String::from("File already exists"));
+        }
+
+        disk::move_path(Path::new(from), Path::new(to)).map_err(|err| err.to_string())?;
+        self.ops.push(DiskOp::Move { from: from.to_string(), to: to.to_string() });
+        Ok(())
+    }
+
+    fn copy(&mut self, from: &str, to: &str) -> Result<(), String> {
+        disk::copy_recursive(Path::new(from), Path::new(to)).map_err(|err| err.to_string())?;
+        self.ops.push(DiskOp::Create { path: to.to_string() });
+        Ok(())
+    }
+
+    fn trash(&mut self, path: &str) -> Result<(), String> {
+        let name = trash::trash(Path::new(path)).map_err(|err| err.to_string())?;
+        self.ops.push(DiskOp::Trash { path: path.to_string(), name });
+        Ok(())
+    }
+
+    fn delete(&mut self, path: &str, staged: &str) -> Result<(), String> {
+        disk::move_path(Path::new(path), Path::new(staged)).map_err(|err| err.to_string())?;
+        self.ops.push(DiskOp::Move { from: path.to_string(), to: staged.to_string() });
+        Ok(())
+    }
+
+    fn restore(&mut self, path: &str, trash_name: &str) -> Result<(), String> {
+        trash::restore(trash_name, Path::new(path)).map_err(|err| err.to_string())?;
+        self.ops.push(DiskOp::Restore { path: path.to_string(), name: trash_name.to_string() });
+        Ok(())
+    }
+
+    fn purge(&mut self, trash_name: &str) -> Result<(), String> {
+        trash::purge(trash_name).map_err(|err| err.to_string())
+    }
+
+    fn trash_items(&self) -> Result<Vec<trash::TrashItem>, String> {
+        trash::list().map_err(|err| err.to_string())
+    }
+
+    fn compress(&mut self, path: &str, format: archive::ArchiveFormat, sources: &[(String, PathBuf)], progress: &mut dyn FnMut(u64, u64)) -> Result<(), String> {
+        if self.exists(path) {
+            return Err(String::from("File already exists"));
+        }
+
+        if let Err(err) = archive::create(Path::new(path), format, sources, progress) {
+            fs::remove_file(path).ok();
+            return Err(err.to_string());
+        }
+
+        self.ops.push(DiskOp::Create { path: path.to_string() });
+        Ok(())
+    }
+
+    fn take_ops(&mut self) -> Vec<DiskOp> {
+        std::mem::take(&mut self.ops)
+    }
+
+    // Everything is on disk already
+    fn save(&self) -> Result<(), String> {
+        Ok(())
+    }
+}
@@ -0,0 +1,133 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::tree;
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub enum FindMode {
+    Name,
+    Glob,
+    Regex,
+}
+
+impl FindMode {
+    pub fn next(&self) -> FindMode {
+        match self {
+            FindMode::Name => FindMode::Glob,
+            FindMode::Glob => FindMode::Regex,
+            FindMode::Regex => FindMode::Name,
+        }
+    }
+
+    pub fn label(&self) -> &str {
+        match self {
+            FindMode::Name => "name",
+            FindMode::Glob => "glob",
+            FindMode::Regex => "regex",
+        }
+    }
+}
+
+// `*` matches any run of characters and `?` a single one, everything else literally
+fn glob_to_regex(glob: &str) -> String {
+    let mut re = String::from("^");
+
+    for c in glob.chars() {
+        match c {
+            '*' => re.push_str(".*"),
+            '?' => re.push('.'),
+            _ => re.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+
+    re.push('$');
+    re
+}
+
+// Searches the names of every entry and link in the tree
+pub fn find(tree: &tree::Tree, query: &str, mode: &FindMode) -> Result<Vec<tree::Entry>, regex::Error> {
+    let matcher: Box<dyn Fn(&str) -> bool> = match mode {
+        FindMode::Name => {
+            let query = query.to_lowercase();
+            Box::new(move |name: &str| name.to_lowercase().contains(&query))
+        },
+        FindMode::Glob => {
+            let re = Regex::new(&glob_to_regex(query))?;
+            Box::new(move |name: &str| re.is_match(name))
+        },
+        FindMode::Regex => {
+            let re = Regex::new(query)?;
+            Box::new(move |name: &str| re.is_match(name))
+        },
+    };
+
+    let mut results: Vec<tree::Entry> = Vec::new();
+
+    for e in tree.root.iter() {
+        if matcher(&e.name) {
+            results.push(e.clone());
+        }
+    }
+
+    for l in tree._links.iter() {
+        if matcher(&l.name) {
+            results.push(tree::Entry {
+                name: l.name.clone(),
+                path: l.path.clone(),
+                r#type: format!("{}l", l.r#type),
+            });
+        }
+    }
+
+    results.sort_by(|a, b| a.path.cmp(&b.path));
+
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn glob_matches(glob: &str, name: &str) -> bool {
+        Regex::new(&glob_to_regex(glob)).unwrap().is_match(name)
+    }
+
+    #[test]
+    fn star_matches_any_run() {
+        assert!(glob_matches("*.rs", "main.rs"));
+        assert!(glob_matches("*.rs", ".rs"));
+        assert!(glob_matches("a*b*c", "a-b-xc"));
+        assert!(!glob_matches("*.rs", "main.rs.bak"));
+    }
+
+    #[test]
+    fn question_mark_matches_one_char() {
+        assert!(glob_matches("file?.txt", "file1.txt"));
+        assert!(glob_matches("?", "é"));
+        assert!(!glob_matches("file?.txt", "file.txt"));
+        assert!(!glob_matches("file?.txt", "file12.txt"));
+    }
+
+    #[test]
+    fn escapes_everything_else() {
+        assert_eq!(glob_to_regex("a.b+(c)"), r"^a\.b\+\(c\)$");
+        assert!(!glob_matches("a.b", "axb"));
+        assert!(glob_matches("[x]", "[x]"));
+    }
+
+    #[test]
+    fn finds_entries_and_links_by_mode() {
+        let tree: tree::Tree = serde_json::from_str(r#"{
+            "root": [{"name": "src", "path": "/src", "type": "d"}, {"name": "main.rs", "path": "/src/main.rs", "type": "f"}],
+            "_links": [{"name": "Main.rs", "path": "/Main.rs", "link_path": "/src/main.rs", "type": "f"}]
+        }"#).unwrap();
+        let paths = |query: &str, mode: FindMode| -> Vec<String> {
+            find(&tree, query, &mode).unwrap().into_iter().map(|e| e.path).collect()
+        };
+
+        assert_eq!(paths("MAIN", FindMode::Name), vec!["/Main.rs", "/src/main.rs"]);
+        assert_eq!(paths("m*.rs", FindMode::Glob), vec!["/src/main.rs"]);
+        assert_eq!(paths("^(src|Main)", FindMode::Regex), vec!["/Main.rs", "/src"]);
+        assert!(find(&tree, "(", &FindMode::Regex).is_err());
+    }
+}
@@ -0,0 +1,165 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+
+use crate::json_file;
+
+// Once the ranks add up to more than this they are all aged and the
+// folders that drop below 1 are forgotten
+const MAX_TOTAL_RANK: f64 = 10000.0;
+const AGING: f64 = 0.9;
+
+const HOUR: u64 = 60 * 60;
+const DAY: u64 = 24 * HOUR;
+const WEEK: u64 = 7 * DAY;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Visits {
+    pub rank: f64,
+    pub last: u64,
+}
+
+// How often and how recently folders were visited
+#[derive(Serialize, Deserialize, Default)]
+pub struct Frecency {
+    #[serde(default)]
+    pub dirs: HashMap<String, Visits>,
+    #[serde(skip)]
+    path: PathBuf,
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+// Every word has to show up in the path in order, and the last one in its last part
+pub fn matches(path: &str, words: &[String]) -> bool {
+    let path = path.to_lowercase();
+    let mut rest = path.as_str();
+
+    for word in words.iter() {
+        match rest.find(word.as_str()) {
+            Some(i) => rest = &rest[i + word.len()..],
+            None => return false,
+        }
+    }
+
+    match words.last() {
+        Some(last) => Path::new(&path).file_name().map(|name| name.to_string_lossy().contains(last.as_str())).unwrap_or(false),
+        None => true,
+    }
+}
+
+pub fn query_words(query: &str) -> Vec<String> {
+    query.split_whitespace().map(|word| word.to_lowercase()).collect()
+}
+
+impl Frecency {
+    // No visits yet, saved to `path`
+    pub fn empty(path: PathBuf) -> Frecency {
+        Frecency { dirs: HashMap::new(), path }
+    }
+
+    pub fn load(path: PathBuf) -> Result<Frecency, String> {
+        match json_file::load::<Frecency>(&path)? {
+            Some(frecency) => Ok(Frecency { path, ..frecency }),
+            None => Ok(Frecency::empty(path)),
+        }
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        json_file::save(&self.path, self)
+    }
+
+    pub fn visit(&mut self, path: &str) {
+        let visits = self.dirs.entry(path.to_string()).or_insert(Visits { rank: 0.0, last: 0 });
+        visits.rank += 1.0;
+        visits.last = now();
+
+        if self.dirs.values().map(|v| v.rank).sum::<f64>() > MAX_TOTAL_RANK {
+            for visits in self.dirs.values_mut() {
+                visits.rank *= AGING;
+            }
+            self.dirs.retain(|_, visits| visits.rank >= 1.0);
+        }
+    }
+
+    // Recent visits count for more, like zoxide does
+    fn score(visits: &Visits, now: u64) -> f64 {
+        let age = now.saturating_sub(visits.last);
+        let factor = if age < HOUR {
+            4.0
+        } else if age < DAY {
+            2.0
+        } else if age < WEEK {
+            0.5
+        } else {
+            0.25
+        };
+
+        visits.rank * factor
+    }
+
+    // Visited folders matching `query`, the best ranked first
+    pub fn ranked(&self, query: &str) -> Vec<String> {
+        let words = query_words(query);
+        let now = now();
+
+        let mut found: Vec<(&String, f64)> = self.dirs.iter()
+            .filter(|(path, _)| matches(path, &words))
+            .map(|(path, visits)| (path, Frecency::score(visits, now)))
+            .collect();
+        found.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal).then_with(|| a.0.cmp(b.0)));
+
+        found.into_iter().map(|(path, _)| path.clone()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(query: &str) -> Vec<String> {
+        query_words(query)
+    }
+
+    #[test]
+    fn matches_words_in_order() {
+        assert!(matches("/home/mats/projects/crate", &words("proj crate")));
+        assert!(matches("/home/mats/projects/crate", &words("HOME Crate")));
+        assert!(!matches("/home/mats/projects/crate", &words("crate proj")));
+        assert!(!matches("/home/mats/projects/crate", &words("nope")));
+    }
+
+    #[test]
+    fn last_word_matches_the_folder_name() {
+        assert!(!matches("/home/mats/projects/crate", &words("projects")));
+        assert!(matches("/home/mats/projects", &words("projects")));
+        assert!(matches("/anything", &[]));
+    }
+
+    #[test]
+    fn ranks_by_visits_and_recency() {
+        let mut frecency = Frecency::empty(PathBuf::new());
+        let now = now();
+        frecency.dirs.insert(String::from("/a/src"), Visits { rank: 3.0, last: now });
+        frecency.dirs.insert(String::from("/b/src"), Visits { rank: 10.0, last: now - 2 * WEEK });
+        frecency.dirs.insert(String::from("/c/src"), Visits { rank: 1.0, last: now });
+        frecency.dirs.insert(String::from("/c/docs"), Visits { rank: 50.0, last: now });
+
+        assert_eq!(frecency.ranked("src"), vec!["/a/src", "/c/src", "/b/src"]);
+        assert_eq!(frecency.ranked("c src"), vec!["/c/src"]);
+    }
+
+    #[test]
+    fn ages_ranks_once_they_add_up() {
+        let mut frecency = Frecency::empty(PathBuf::new());
+        frecency.dirs.insert(String::from("/old"), Visits { rank: 1.05, last: 0 });
+        frecency.dirs.insert(String::from("/busy"), Visits { rank: MAX_TOTAL_RANK, last: 0 });
+        frecency.visit("/new");
+
+        assert!(!frecency.dirs.contains_key("/old"));
+        assert_eq!(frecency.dirs["/busy"].rank, MAX_TOTAL_RANK * AGING);
+    }
+}
@@ -0,0 +1,50 @@
+// Matches `query` as a subsequence of `text` and returns the char positions that matched.
+// The match is case insensitive unless the query contains an uppercase letter.
+pub fn fuzzy_match(query: &str, text: &str) -> Option<Vec<usize>> {
+    let case_sensitive = query.chars().any(|c| c.is_uppercase());
+    let normalize = |c: char| if case_sensitive { c } else { c.to_ascii_lowercase() };
+
+    let mut positions: Vec<usize> = Vec::new();
+    let mut query_chars = query.chars().map(normalize).peekable();
+
+    for (i, c) in text.chars().enumerate() {
+        match query_chars.peek() {
+            Some(q) if *q == normalize(c) => {
+                positions.push(i);
+                query_chars.next();
+            },
+            Some(_) => {},
+            None => break,
+        }
+    }
+
+    if query_chars.peek().is_none() {
+        Some(positions)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_a_subsequence() {
+        assert_eq!(fuzzy_match("mrs", "main.rs"), Some(vec![0, 5, 6]));
+        assert_eq!(fuzzy_match("", "main.rs"), Some(vec![]));
+        assert_eq!(fuzzy_match("sm", "main.rs"), None);
+    }
+
+    #[test]
+    fn ignores_case_unless_the_query_has_uppercase() {
+        assert_eq!(fuzzy_match("readme", "README.md"), Some(vec![0, 1, 2, 3, 4, 5]));
+        assert_eq!(fuzzy_match("Read", "readme"), None);
+        assert_eq!(fuzzy_match("RM", "README.md"), Some(vec![0, 4]));
+    }
+
+    #[test]
+    fn counts_positions_in_chars() {
+        assert_eq!(fuzzy_match("ét", "café été"), Some(vec![3, 6]));
+    }
+}
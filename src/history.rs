@@ -0,0 +1,134 @@
+use std::collections::HashMap;
+
+const MAX_HISTORY: usize = 100;
+
+// Folders visited this session and where the cursor was left in each of them
+pub struct History {
+    paths: Vec<String>,
+    curr: usize,
+    positions: HashMap<String, (usize, i32)>,
+}
+
+impl History {
+    pub fn new(start_path: &str) -> History {
+        History {
+            paths: vec![start_path.to_string()],
+            curr: 0,
+            positions: HashMap::new(),
+        }
+    }
+
+    // Going somewhere new drops the folders that were ahead of the current one
+    pub fn visit(&mut self, path: &str) {
+        if self.paths.get(self.curr).map(|p| p == path).unwrap_or(false) {
+            return;
+        }
+
+        self.paths.truncate(self.curr + 1);
+        self.paths.push(path.to_string());
+
+        if self.paths.len() > MAX_HISTORY {
+            self.paths.remove(0);
+        }
+        self.curr = self.paths.len() - 1;
+    }
+
+    pub fn back(&mut self) -> Option<String> {
+        if self.curr == 0 {
+            return None;
+        }
+
+        self.curr -= 1;
+        Some(self.paths[self.curr].clone())
+    }
+
+    pub fn forward(&mut self) -> Option<String> {
+        if self.curr + 1 >= self.paths.len() {
+            return None;
+        }
+
+        self.curr += 1;
+        Some(self.paths[self.curr].clone())
+    }
+
+    // Every visited folder once, the most recent first
+    pub fn recent(&self) -> Vec<String> {
+        let mut recent: Vec<String> = vec![];
+
+        for path in self.paths.iter().rev() {
+            if !recent.contains(path) {
+                recent.push(path.clone());
+            }
+        }
+
+        recent
+    }
+
+    pub fn save_position(&mut self, path: &str, file_curr: usize, top_offset: i32) {
+        self.positions.insert(path.to_string(), (file_curr, top_offset));
+    }
+
+    pub fn position(&self, path: &str) -> (usize, i32) {
+        self.positions.get(path).copied().unwrap_or((0, 0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn goes_back_and_forward() {
+        let mut history = History::new("/");
+        history.visit("/a");
+        history.visit("/a/b");
+
+        assert_eq!(history.back().as_deref(), Some("/a"));
+        assert_eq!(history.back().as_deref(), Some("/"));
+        assert_eq!(history.back(), None);
+        assert_eq!(history.forward().as_deref(), Some("/a"));
+        assert_eq!(history.forward().as_deref(), Some("/a/b"));
+        assert_eq!(history.forward(), None);
+    }
+
+    #[test]
+    fn visiting_drops_the_folders_ahead() {
+        let mut history = History::new("/");
+        history.visit("/a");
+        history.visit("/b");
+        history.back();
+        history.visit("/c");
+
+        assert_eq!(history.forward(), None);
+        assert_eq!(history.back().as_deref(), Some("/a"));
+    }
+
+    #[test]
+    fn ignores_the_current_folder() {
+        let mut history = History::new("/");
+        history.visit("/");
+
+        assert_eq!(history.back(), None);
+    }
+
+    #[test]
+    fn keeps_the_latest_folders() {
+        let mut history = History::new("/");
+        for i in 0..MAX_HISTORY + 10 {
+            history.visit(&format!("/{}", i));
+        }
+
+        assert_eq!(history.recent().len(), MAX_HISTORY);
+        assert_eq!(history.recent()[0], format!("/{}", MAX_HISTORY + 9));
+    }
+
+    #[test]
+    fn lists_recent_folders_once() {
+        let mut history = History::new("/");
+        history.visit("/a");
+        history.visit("/b");
+        history.visit("/a");
+
+        assert_eq!(history.recent(), vec!["/a", "/b", "/"]);
+    }
+}
@@ -0,0 +1,331 @@
+use std::fs::create_dir_all;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::backend::TreeStore;
+use crate::disk;
+use crate::trash;
+use crate::tree;
+
+const MAX_CHANGES: usize = 100;
+
+pub enum DiskOp {
+    // `from` was moved to `to`, deleted entries are moved into the staging area
+    Move { from: String, to: String },
+    // `path` was created, undoing it moves it to the staging area so redo can bring it back
+    Create { path: String },
+    // `path` was moved to the trash as `name`
+    Trash { path: String, name: String },
+    // `path` was restored from the trash where it was stored as `name`
+    Restore { path: String, name: String },
+}
+
+// Something the tree keeps a list of
+#[derive(Clone)]
+pub enum Record {
+    Entry(tree::Entry),
+    Link(tree::Link),
+    Trashed(tree::Trashed),
+}
+
+pub enum TreeOp {
+    // `record` was inserted at `index` of its list
+    Add { index: usize, record: Record },
+    // `record` was taken out of its list at `index`
+    Remove { index: usize, record: Record },
+    // Records at or below `from` were moved to `to`
+    Rename { from: String, to: String },
+}
+
+impl TreeOp {
+    pub fn inverse(&self) -> TreeOp {
+        match self {
+            TreeOp::Add { index, record } => TreeOp::Remove { index: *index, record: record.clone() },
+            TreeOp::Remove { index, record } => TreeOp::Add { index: *index, record: record.clone() },
+            TreeOp::Rename { from, to } => TreeOp::Rename { from: to.clone(), to: from.clone() },
+        }
+    }
+}
+
+pub struct Change {
+    id: usize,
+    ops: Vec<DiskOp>,
+    tree_ops: Vec<TreeOp>,
+}
+
+pub struct Journal {
+    undo: Vec<Change>,
+    redo: Vec<Change>,
+    staging: PathBuf,
+    counter: usize,
+}
+
+impl Journal {
+    pub fn new(staging: PathBuf) -> Journal {
+        Journal {
+            undo: vec![],
+            redo: vec![],
+            staging,
+            counter: 0,
+        }
+    }
+
+    pub fn stage_path(&mut self) -> io::Result<String> {
+        create_dir_all(&self.staging)?;
+        self.counter += 1;
+
+        Ok(format!("{}/{}", self.staging.display(), self.counter))
+    }
+
+    pub fn record(&mut self, ops: Vec<DiskOp>, tree_ops: Vec<TreeOp>) {
+        if ops.is_empty() && tree_ops.is_empty() {
+            return;
+        }
+
+        for change in self.redo.drain(..) {
+            prune(&self.staging, &change);
+        }
+
+        self.counter += 1;
+        self.undo.push(Change { id: self.counter, ops, tree_ops });
+
+        if self.undo.len() > MAX_CHANGES {
+            let change = self.undo.remove(0);
+            prune(&self.staging, &change);
+        }
+    }
+
+    // The tree is changed back op by op, so what happened to it outside of the journal
+    // stays. Returns false when there was nothing to undo. A change that can't be undone
+    // is put back the way it was and dropped, so the ones before it can still be undone.
+    pub fn undo(&mut self, tree: Option<&mut dyn TreeStore>) -> io::Result<bool> {
+        let change = match self.undo.pop() {
+            Some(change) => change,
+            None => return Ok(false),
+        };
+
+        for (i, op) in change.ops.iter().enumerate().rev() {
+            if let Err(err) = self.undo_op(&change, i, op) {
+                for (j, op) in change.ops.iter().enumerate().skip(i + 1) {
+                    self.redo_op(&change, j, op).ok();
+                }
+
+                prune(&self.staging, &change);
+                return Err(err);
+            }
+        }
+
+        if let Some(tree) = tree {
+            for op in change.tree_ops.iter().rev() {
+                tree.apply(&op.inverse());
+            }
+        }
+
+        self.redo.push(change);
+
+        Ok(true)
+    }
+
+    // Returns false when there was nothing to redo. Like with undo, a change that
+    // can't be redone is dropped.
+    pub fn redo(&mut self, tree: Option<&mut dyn TreeStore>) -> io::Result<bool> {
+        let change = match self.redo.pop() {
+            Some(change) => change,
+            None => return Ok(false),
+        };
+
+        for (i, op) in change.ops.iter().enumerate() {
+            if let Err(err) = self.redo_op(&change, i, op) {
+                for (j, op) in change.ops.iter().enumerate().take(i).rev() {
+                    self.undo_op(&change, j, op).ok();
+                }
+
+                prune(&self.staging, &change);
+                return Err(err);
+            }
+        }
+
+        if let Some(tree) = tree {
+            for op in change.tree_ops.iter() {
+                tree.apply(op);
+            }
+        }
+
+        self.undo.push(change);
+
+        Ok(true)
+    }
+
+    fn undo_op(&self, change: &Change, i: usize, op: &DiskOp) -> io::Result<()> {
+        match op {
+            DiskOp::Move { from, to } => disk::move_path(Path::new(to), Path::new(from)),
+            DiskOp::Create { path } => {
+                create_dir_all(&self.staging).and_then(|_| disk::move_path(Path::new(path), &staged(&self.staging, change.id, i)))
+            },
+            DiskOp::Trash { path, name } => trash::restore(name, Path::new(path)),
+            DiskOp::Restore { path, name } => trash::trash_as(Path::new(path), name),
+        }
+    }
+
+    fn redo_op(&self, change: &Change, i: usize, op: &DiskOp) -> io::Result<()> {
+        match op {
+            DiskOp::Move { from, to } => disk::move_path(Path::new(from), Path::new(to)),
+            DiskOp::Create { path } => disk::move_path(&staged(&self.staging, change.id, i), Path::new(path)),
+            DiskOp::Trash { path, name } => trash::trash_as(Path::new(path), name),
+            DiskOp::Restore { path, name } => trash::restore(name, Path::new(path)),
+        }
+    }
+
+    // Permanently removes everything in the staging area
+    pub fn clear(&mut self) {
+        for change in self.undo.drain(..).chain(self.redo.drain(..)) {
+            prune(&self.staging, &change);
+        }
+    }
+}
+
+// Where undoing the `i`th op of a change moves what it created
+fn staged(staging: &Path, id: usize, i: usize) -> PathBuf {
+    staging.join(format!("{}-{}", id, i))
+}
+
+fn prune(staging: &Path, change: &Change) {
+    for (i, op) in change.ops.iter().enumerate() {
+        let paths = match op {
+            DiskOp::Move { from, to } => [PathBuf::from(from), PathBuf::from(to)],
+            DiskOp::Create { path } => [PathBuf::from(path), staged(staging, change.id, i)],
+            DiskOp::Trash { .. } | DiskOp::Restore { .. } => continue,
+        };
+
+        for path in paths {
+            if path.starts_with(staging) && path.symlink_metadata().is_ok() {
+                disk::remove_path(&path).ok();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    struct Dir(PathBuf);
+
+    impl Dir {
+        fn new(name: &str) -> Dir {
+            let dir = std::env::temp_dir().join(format!("journal-test-{}-{}", name, std::process::id()));
+            fs::create_dir_all(&dir).unwrap();
+            Dir(dir)
+        }
+
+        fn path(&self, name: &str) -> String {
+            self.0.join(name).display().to_string()
+        }
+
+        fn has(&self, name: &str) -> bool {
+            self.0.join(name).exists()
+        }
+
+        fn touch(&self, name: &str) {
+            fs::write(self.0.join(name), "").unwrap();
+        }
+    }
+
+    impl Drop for Dir {
+        fn drop(&mut self) {
+            fs::remove_dir_all(&self.0).ok();
+        }
+    }
+
+    fn moved(dir: &Dir, from: &str, to: &str) -> DiskOp {
+        fs::rename(dir.path(from), dir.path(to)).unwrap();
+        DiskOp::Move { from: dir.path(from), to: dir.path(to) }
+    }
+
+    fn created(dir: &Dir, name: &str) -> DiskOp {
+        dir.touch(name);
+        DiskOp::Create { path: dir.path(name) }
+    }
+
+    #[test]
+    fn undoes_and_redoes_changes_in_order() {
+        let dir = Dir::new("order");
+        let mut journal = Journal::new(dir.0.join("staging"));
+        dir.touch("a");
+
+        let op = moved(&dir, "a", "b");
+        journal.record(vec![op], vec![]);
+        let op = created(&dir, "c");
+        journal.record(vec![op], vec![]);
+
+        assert!(journal.undo(None).unwrap());
+        assert!(!dir.has("c") && dir.has("b"));
+        assert!(journal.undo(None).unwrap());
+        assert!(dir.has("a") && !dir.has("b"));
+        assert!(!journal.undo(None).unwrap());
+
+        assert!(journal.redo(None).unwrap());
+        assert!(dir.has("b"));
+        assert!(journal.redo(None).unwrap());
+        assert!(dir.has("c"));
+        assert!(!journal.redo(None).unwrap());
+    }
+
+    #[test]
+    fn a_new_change_clears_redo() {
+        let dir = Dir::new("clear");
+        let mut journal = Journal::new(dir.0.join("staging"));
+
+        let op = created(&dir, "a");
+        journal.record(vec![op], vec![]);
+        journal.undo(None).unwrap();
+
+        let op = created(&dir, "b");
+        journal.record(vec![op], vec![]);
+        assert!(!journal.redo(None).unwrap());
+        assert!(!dir.has("a"));
+    }
+
+    #[test]
+    fn a_change_that_cant_be_undone_is_dropped() {
+        let dir = Dir::new("undo");
+        let mut journal = Journal::new(dir.0.join("staging"));
+        dir.touch("a");
+        dir.touch("d");
+
+        let op = moved(&dir, "a", "b");
+        journal.record(vec![op], vec![]);
+        let ops = vec![moved(&dir, "d", "e"), created(&dir, "c")];
+        journal.record(ops, vec![]);
+
+        // Gone from where the change put it, like a purged trash item
+        fs::remove_file(dir.path("e")).unwrap();
+
+        assert!(journal.undo(None).is_err());
+        assert!(dir.has("c"));
+
+        assert!(journal.undo(None).unwrap());
+        assert!(dir.has("a"));
+        assert!(!journal.undo(None).unwrap());
+    }
+
+    #[test]
+    fn a_change_that_cant_be_redone_is_dropped() {
+        let dir = Dir::new("redo");
+        let mut journal = Journal::new(dir.0.join("staging"));
+        dir.touch("a");
+
+        let ops = vec![created(&dir, "c"), moved(&dir, "a", "b")];
+        journal.record(ops, vec![]);
+        journal.undo(None).unwrap();
+
+        // Taken by something else in the meantime
+        fs::remove_file(dir.path("a")).unwrap();
+
+        assert!(journal.redo(None).is_err());
+        assert!(!dir.has("c"));
+        assert!(!journal.redo(None).unwrap());
+        assert!(!journal.undo(None).unwrap());
+    }
+}
@@ -0,0 +1,24 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+// Bookmarks, frecency, the session and the journal are kept in the folder of tree.json
+pub fn next_to_tree(tree_path: &Path, name: &str) -> PathBuf {
+    tree_path.parent().unwrap_or(Path::new("")).join(name)
+}
+
+// None when the file doesn't exist yet
+pub fn load<T: DeserializeOwned>(path: &Path) -> Result<Option<T>, String> {
+    match fs::read_to_string(path) {
+        Ok(data) => serde_json::from_str(&data).map(Some).map_err(|err| format!("{}: {}", path.display(), err)),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(format!("{}: {}", path.display(), err)),
+    }
+}
+
+pub fn save<T: Serialize>(path: &Path, value: &T) -> Result<(), String> {
+    let data = serde_json::to_string(value).map_err(|err| err.to_string())?;
+    fs::write(path, data).map_err(|err| format!("{}: {}", path.display(), err))
+}
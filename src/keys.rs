@@ -0,0 +1,369 @@
+use std::collections::HashMap;
+use serde::Deserialize;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Action {
+    Quit,
+    Up,
+    Down,
+    Back,
+    EnterDir,
+    Open,
+    NewFile,
+    NewDir,
+    NewLink,
+    Rename,
+    Select,
+    Delete,
+    DeletePermanently,
+    Trash,
+    Yank,
+    Cut,
+    Paste,
+    Undo,
+    Redo,
+    Reload,
+    HardReload,
+    Sync,
+    Search,
+    NextMatch,
+    PrevMatch,
+    Find,
+    Sort,
+    ReverseSort,
+    Columns,
+    Preview,
+    Compress,
+    NewTab,
+    CloseTab,
+    NextTab,
+    PrevTab,
+    GoToTab(usize),
+    CopyToTab,
+    MoveToTab,
+    TogglePanes,
+    SwitchPane,
+    Mark,
+    JumpToMark,
+    Bookmarks,
+    GoTo,
+    Jump,
+    HistoryBack,
+    HistoryForward,
+    Recent,
+}
+
+impl Action {
+    // Actions that change files or the tree, these are disabled with --read-only
+    pub fn is_mutating(&self) -> bool {
+        matches!(self,
+            Action::NewFile | Action::NewDir | Action::NewLink | Action::Rename | Action::Delete |
+            Action::DeletePermanently | Action::Cut | Action::Paste | Action::Undo | Action::Redo |
+            Action::Compress | Action::CopyToTab | Action::MoveToTab)
+    }
+}
+
+// Name used in the config file, the action and its default keys
+const ACTIONS: &[(&str, Action, &[&str])] = &[
+    ("quit", Action::Quit, &["q"]),
+    ("up", Action::Up, &["k"]),
+    ("down", Action::Down, &["j"]),
+    ("back", Action::Back, &["h"]),
+    ("enter_dir", Action::EnterDir, &["l"]),
+    ("open", Action::Open, &["<Enter>"]),
+    ("new_file", Action::NewFile, &["o"]),
+    ("new_dir", Action::NewDir, &["O"]),
+    ("new_link", Action::NewLink, &["p"]),
+    ("rename", Action::Rename, &["c"]),
+    ("select", Action::Select, &["v"]),
+    ("delete", Action::Delete, &["d"]),
+    ("delete_permanently", Action::DeletePermanently, &["D"]),
+    ("trash", Action::Trash, &["T"]),
+    ("yank", Action::Yank, &["y"]),
+    ("cut", Action::Cut, &["x"]),
+    ("paste", Action::Paste, &["P"]),
+    ("undo", Action::Undo, &["u"]),
+    ("redo", Action::Redo, &["<C-r>"]),
+    ("reload", Action::Reload, &["r"]),
+    ("hard_reload", Action::HardReload, &["R"]),
+    ("sync", Action::Sync, &["S"]),
+    ("search", Action::Search, &["/"]),
+    ("next_match", Action::NextMatch, &["n"]),
+    ("prev_match", Action::PrevMatch, &["N"]),
+    ("find", Action::Find, &["F"]),
+    ("sort", Action::Sort, &["s"]),
+    ("reverse_sort", Action::ReverseSort, &["I"]),
+    ("columns", Action::Columns, &["C"]),
+    ("preview", Action::Preview, &["w"]),
+    ("compress", Action::Compress, &["a"]),
+    ("new_tab", Action::NewTab, &["<C-t>"]),
+    ("close_tab", Action::CloseTab, &["<C-w>"]),
+    ("next_tab", Action::NextTab, &["gt"]),
+    ("prev_tab", Action::PrevTab, &["gT"]),
+    ("tab_1", Action::GoToTab(0), &["1"]),
+    ("tab_2", Action::GoToTab(1), &["2"]),
+    ("tab_3", Action::GoToTab(2), &["3"]),
+    ("tab_4", Action::GoToTab(3), &["4"]),
+    ("tab_5", Action::GoToTab(4), &["5"]),
+    ("tab_6", Action::GoToTab(5), &["6"]),
+    ("tab_7", Action::GoToTab(6), &["7"]),
+    ("tab_8", Action::GoToTab(7), &["8"]),
+    ("tab_9", Action::GoToTab(8), &["9"]),
+    ("copy_to_tab", Action::CopyToTab, &["Y"]),
+    ("move_to_tab", Action::MoveToTab, &["M"]),
+    ("toggle_panes", Action::TogglePanes, &["W"]),
+    ("history_forward", Action::HistoryForward, &["<C-f>"]),
+    ("switch_pane", Action::SwitchPane, &["<Tab>"]),
+    ("mark", Action::Mark, &["m"]),
+    ("jump_to_mark", Action::JumpToMark, &["'"]),
+    ("bookmarks", Action::Bookmarks, &["B"]),
+    ("go_to", Action::GoTo, &[":"]),
+    ("jump", Action::Jump, &["z"]),
+    ("history_back", Action::HistoryBack, &["<C-o>"]),
+    ("recent", Action::Recent, &["H"]),
+];
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum KeyList {
+    One(String),
+    Many(Vec<String>),
+}
+
+// Turns "gt", "<C-r>" or "<Enter>" into the key codes getch() returns
+fn parse_keys(keys: &str) -> Result<Vec<i32>, String> {
+    let mut codes: Vec<i32> = vec![];
+    let mut rest = keys;
+
+    while let Some(c) = rest.chars().next() {
+        if c == '<' {
+            if let Some(end) = rest.find('>') {
+                let name = &rest[1..end];
+                let code = match name {
+                    "Enter" | "CR" => 10,
+                    "Tab" => 9,
+                    "Space" => 32,
+                    "BS" => 127,
+                    "lt" => '<' as i32,
+                    _ => match name.strip_prefix("C-") {
+                        Some(ch) if ch.len() == 1 && ch.chars().all(|ch| ch.is_ascii_alphabetic()) => (ch.as_bytes()[0].to_ascii_lowercase() & 0x1f) as i32,
+                        _ => return Err(format!("Unknown key <{}> in \"{}\"", name, keys)),
+                    },
+                };
+
+                codes.push(code);
+                rest = &rest[end + 1..];
+                continue;
+            }
+        }
+
+        codes.push(c as i32);
+        rest = &rest[c.len_utf8()..];
+    }
+
+    if codes.is_empty() {
+        return Err(String::from("Empty key binding"));
+    }
+
+    Ok(codes)
+}
+
+// Reverse of parse_keys, used to show the bound keys in hints
+fn key_name(code: i32) -> String {
+    match code {
+        10 => String::from("enter"),
+        9 => String::from("tab"),
+        32 => String::from("space"),
+        127 => String::from("backspace"),
+        1..=26 => format!("C-{}", (code as u8 + b'a' - 1) as char),
+        _ => char::from_u32(code as u32).map(String::from).unwrap_or_else(|| String::from("?")),
+    }
+}
+
+// A key that starts a longer sequence waits for the next key, so it can't be bound on its own.
+// A key sequence can only be bound to one action.
+pub struct KeyMap {
+    bindings: HashMap<Vec<i32>, Action>,
+    pending: Vec<i32>,
+}
+
+impl KeyMap {
+    pub fn new(overrides: &HashMap<String, KeyList>) -> Result<KeyMap, String> {
+        let mut bindings: HashMap<Vec<i32>, Action> = HashMap::new();
+
+        for name in overrides.keys() {
+            if !ACTIONS.iter().any(|(n, _, _)| n == name) {
+                return Err(format!("Unknown action \"{}\" in [keys]", name));
+            }
+        }
+
+        // Action names and keys as written by key sequence, to tell which ones clash
+        let mut bound: HashMap<Vec<i32>, (&str, String)> = HashMap::new();
+
+        for (name, action, defaults) in ACTIONS.iter() {
+            let keys: Vec<String> = match overrides.get(*name) {
+                Some(KeyList::One(key)) => vec![key.clone()],
+                Some(KeyList::Many(keys)) => keys.clone(),
+                None => defaults.iter().map(|key| key.to_string()).collect(),
+            };
+
+            for key in keys {
+                let codes = parse_keys(&key)?;
+
+                if let Some((other, _)) = bound.get(&codes).filter(|(other, _)| other != name) {
+                    return Err(format!("Key \"{}\" is bound to both {} and {}", key, other, name));
+                }
+
+                let prefix = bound.iter().find(|(keys, _)| keys.len() != codes.len() && (keys.starts_with(&codes) || codes.starts_with(keys)));
+                if let Some((keys, (other, other_key))) = prefix {
+                    let (short, long) = if keys.len() < codes.len() { ((other, other_key), (name, &key)) } else { ((name, &key), (other, other_key)) };
+                    return Err(format!("Key \"{}\" of {} starts \"{}\" of {}, so it can't be bound on its own", short.1, short.0, long.1, long.0));
+                }
+
+                bound.insert(codes.clone(), (name, key));
+                bindings.insert(codes, *action);
+            }
+        }
+
+        Ok(KeyMap { bindings, pending: vec![] })
+    }
+
+    // Shortest key sequence bound to the action, empty when it has none
+    pub fn key_label(&self, action: Action) -> String {
+        self.bindings.iter()
+            .filter(|(_, bound)| **bound == action)
+            .map(|(keys, _)| keys)
+            .min_by(|a, b| a.len().cmp(&b.len()).then(a.cmp(b)))
+            .map(|keys| keys.iter().map(|c| key_name(*c)).collect())
+            .unwrap_or_default()
+    }
+
+    pub fn reset(&mut self) {
+        self.pending.clear();
+    }
+
+    // Keys typed so far of a sequence that isn't complete yet
+    pub fn pending(&self) -> String {
+        self.pending.iter().map(|c| char::from_u32(*c as u32).unwrap_or('?')).collect()
+    }
+
+    pub fn feed(&mut self, c: i32) -> Option<Action> {
+        self.pending.push(c);
+
+        let pending = &self.pending;
+        if self.bindings.keys().any(|keys| keys.len() > pending.len() && keys.starts_with(pending)) {
+            return None;
+        }
+
+        if let Some(action) = self.bindings.get(&self.pending) {
+            self.pending.clear();
+            return Some(*action);
+        }
+
+        // Start over from the last key when the sequence doesn't lead anywhere
+        let retry = self.pending.len() > 1;
+        self.pending.clear();
+
+        if retry {
+            self.feed(c)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn overrides(keys: &[(&str, &str)]) -> HashMap<String, KeyList> {
+        keys.iter().map(|(name, key)| (name.to_string(), KeyList::One(key.to_string()))).collect()
+    }
+
+    #[test]
+    fn parses_plain_and_named_keys() {
+        assert_eq!(parse_keys("gt"), Ok(vec!['g' as i32, 't' as i32]));
+        assert_eq!(parse_keys("<C-r>"), Ok(vec![18]));
+        assert_eq!(parse_keys("<C-R>"), Ok(vec![18]));
+        assert_eq!(parse_keys("<Enter>"), Ok(vec![10]));
+        assert_eq!(parse_keys("d<Space>"), Ok(vec!['d' as i32, 32]));
+        assert_eq!(parse_keys("<lt>"), Ok(vec!['<' as i32]));
+        assert_eq!(parse_keys("é"), Ok(vec!['é' as i32]));
+    }
+
+    #[test]
+    fn a_lone_lt_is_a_plain_key() {
+        assert_eq!(parse_keys("<"), Ok(vec!['<' as i32]));
+        assert_eq!(parse_keys("a<b"), Ok(vec!['a' as i32, '<' as i32, 'b' as i32]));
+    }
+
+    #[test]
+    fn rejects_unknown_and_empty_keys() {
+        assert!(parse_keys("<Foo>").is_err());
+        assert!(parse_keys("<C-1>").is_err());
+        assert!(parse_keys("").is_err());
+    }
+
+    #[test]
+    fn labels_show_the_shortest_binding() {
+        let keymap = KeyMap::new(&HashMap::new()).unwrap();
+        assert_eq!(keymap.key_label(Action::Redo), "C-r");
+        assert_eq!(keymap.key_label(Action::Open), "enter");
+        assert_eq!(keymap.key_label(Action::NextTab), "gt");
+    }
+
+    #[test]
+    fn sequences_wait_for_the_next_key() {
+        let mut keymap = KeyMap::new(&HashMap::new()).unwrap();
+
+        assert!(keymap.feed('g' as i32).is_none());
+        assert_eq!(keymap.pending(), "g");
+        assert!(keymap.feed('t' as i32) == Some(Action::NextTab));
+        assert_eq!(keymap.pending(), "");
+
+        // A key that doesn't continue the sequence starts a new one
+        assert!(keymap.feed('g' as i32).is_none());
+        assert!(keymap.feed('q' as i32) == Some(Action::Quit));
+
+        keymap.feed('g' as i32);
+        keymap.reset();
+        assert!(keymap.feed('T' as i32) == Some(Action::Trash));
+    }
+
+    #[test]
+    fn defaults_dont_clash() {
+        assert!(KeyMap::new(&HashMap::new()).is_ok());
+    }
+
+    #[test]
+    fn rejects_a_key_bound_to_two_actions() {
+        let err = KeyMap::new(&overrides(&[("trash", "gt")])).err().unwrap();
+        assert!(err.contains("next_tab") && err.contains("trash"), "{}", err);
+
+        // <C-i> is the same key as <Tab>
+        assert!(KeyMap::new(&overrides(&[("history_forward", "<C-i>")])).is_err());
+    }
+
+    #[test]
+    fn rejects_a_key_that_starts_a_longer_sequence() {
+        let err = KeyMap::new(&overrides(&[("quit", "g")])).err().unwrap();
+        assert_eq!(err, "Key \"g\" of quit starts \"gt\" of next_tab, so it can't be bound on its own");
+
+        let err = KeyMap::new(&overrides(&[("recent", "qq")])).err().unwrap();
+        assert_eq!(err, "Key \"q\" of quit starts \"qq\" of recent, so it can't be bound on its own");
+    }
+
+    #[test]
+    fn remapped_keys_replace_the_defaults() {
+        let mut keymap = KeyMap::new(&overrides(&[("trash", "<C-d>"), ("next_tab", "<C-n>")])).unwrap();
+
+        assert!(keymap.feed('T' as i32).is_none());
+        assert!(keymap.feed(4) == Some(Action::Trash));
+        assert!(keymap.feed(14) == Some(Action::NextTab));
+    }
+
+    #[test]
+    fn rejects_unknown_actions() {
+        assert!(KeyMap::new(&overrides(&[("fly", "f")])).is_err());
+    }
+}
@@ -2,20 +2,67 @@ extern crate ncurses;
 
 use ncurses::*;
 use std::cmp::*;
-use std::fs::*;
-use std::path::Path;
-use serde_json::to_writer;
-
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+mod archive;
+mod args;
+mod backend;
+mod bookmarks;
+mod columns;
+mod complete;
+mod config;
+mod date;
+mod disk;
+mod disk_backend;
+mod find;
+mod frecency;
+mod fuzzy;
+mod get_entries;
+mod history;
+mod json_file;
+mod journal;
+mod keys;
+mod preview;
+mod reconcile;
+mod session;
+mod sort;
+mod tabs;
+mod trash;
 mod tree;
+mod tree_backend;
 
 const REGULAR_PAIR: i16 = 0;
 const HIGHLIGHT_PAIR: i16 = 1;
+const ERROR_PAIR: i16 = 2;
+const FILE_PAIR: i16 = 3;
+const DIR_PAIR: i16 = 4;
+const LINK_PAIR: i16 = 5;
 
 enum CommandType {
     NewFile,
     NewDir,
     Delete,
+    PermanentDelete,
+    Trash,
+    Sync,
+    Search,
+    Find,
+    FindResults,
+    Columns,
     NewLink,
+    Rename,
+    Compress,
+    CopyToTab,
+    MoveToTab,
+    Mark,
+    JumpToMark,
+    Bookmarks,
+    NewBookmark,
+    GoTo,
+    Jump,
+    Recent,
     Error(String),
     None,
 }
@@ -23,10 +70,39 @@ enum CommandType {
 struct Ui {
     curr_path: String,
     parent_path: String,
+    tabs: Vec<tabs::Tab>,
+    tab_curr: usize,
+    other_pane: Option<tabs::Tab>,
     command: CommandType,
     input_value: String,
     input_cursor: i32,
-    tree: tree::Tree,
+    completions: Vec<String>,
+    backend: Box<dyn backend::Backend>,
+    clipboard: Vec<tree::Entry>,
+    clipboard_cut: bool,
+    journal: journal::Journal,
+    trash: Vec<trash::TrashItem>,
+    trash_curr: usize,
+    sync: Vec<reconcile::Diff>,
+    sync_curr: usize,
+    search: String,
+    find_mode: find::FindMode,
+    archive_format: archive::ArchiveFormat,
+    find: Vec<tree::Entry>,
+    find_curr: usize,
+    bookmarks: bookmarks::Bookmarks,
+    bookmarks_curr: usize,
+    history: history::History,
+    frecency: frecency::Frecency,
+    recent: Vec<String>,
+    recent_curr: usize,
+    sort_orders: HashMap<String, sort::SortOrder>,
+    columns: columns::Columns,
+    metadata: columns::MetadataCache,
+    preview: bool,
+    config: config::Config,
+    keymap: keys::KeyMap,
+    read_only: bool,
 }
 
 impl Ui {
@@ -38,57 +114,214 @@ impl Ui {
                 let str = format!("New file name: {}", self.input_value);
                 addstr(&str as &str);
 
-                let cursor = self.input_cursor as usize;
                 mv(height - 1, 18 + self.input_cursor);
                 attron(COLOR_PAIR(HIGHLIGHT_PAIR));
-                addstr(self.input_value.get(cursor..=cursor).unwrap_or(" "));
+                addstr(&self.cursor_char());
                 attroff(COLOR_PAIR(HIGHLIGHT_PAIR));
             },
             CommandType::NewDir => {
                 let str = format!("New folder name: {}", self.input_value);
                 addstr(&str as &str);
 
-                let cursor = self.input_cursor as usize;
                 mv(height - 1, 20 + self.input_cursor);
                 attron(COLOR_PAIR(HIGHLIGHT_PAIR));
-                addstr(self.input_value.get(cursor..=cursor).unwrap_or(" "));
+                addstr(&self.cursor_char());
                 attroff(COLOR_PAIR(HIGHLIGHT_PAIR));
             },
             CommandType::NewLink => {
-                let str = format!("New link path: {}", self.input_value);
+                let prefix = format!("New link in {} to: ", self.link_dir());
+                let str = format!("{}{}", prefix, self.input_value);
+                addstr(&str as &str);
+
+                mv(height - 1, 3 + prefix.len() as i32 + self.input_cursor);
+                attron(COLOR_PAIR(HIGHLIGHT_PAIR));
+                addstr(&self.cursor_char());
+                attroff(COLOR_PAIR(HIGHLIGHT_PAIR));
+                self.draw_completions(width, height);
+            },
+            CommandType::GoTo => {
+                let str = format!(":cd {}", self.input_value);
+                addstr(&str as &str);
+
+                mv(height - 1, 7 + self.input_cursor);
+                attron(COLOR_PAIR(HIGHLIGHT_PAIR));
+                addstr(&self.cursor_char());
+                attroff(COLOR_PAIR(HIGHLIGHT_PAIR));
+                self.draw_completions(width, height);
+            },
+            CommandType::Jump => {
+                let str = format!("z {}", self.input_value);
+                addstr(&str as &str);
+
+                mv(height - 1, 5 + self.input_cursor);
+                attron(COLOR_PAIR(HIGHLIGHT_PAIR));
+                addstr(&self.cursor_char());
+                attroff(COLOR_PAIR(HIGHLIGHT_PAIR));
+            },
+            CommandType::Rename => {
+                let str = format!("Rename to: {}", self.input_value);
+                addstr(&str as &str);
+
+                mv(height - 1, 14 + self.input_cursor);
+                attron(COLOR_PAIR(HIGHLIGHT_PAIR));
+                addstr(&self.cursor_char());
+                attroff(COLOR_PAIR(HIGHLIGHT_PAIR));
+            },
+            CommandType::Search => {
+                let str = format!("/{}", self.input_value);
+                addstr(&str as &str);
+
+                mv(height - 1, 4 + self.input_cursor);
+                attron(COLOR_PAIR(HIGHLIGHT_PAIR));
+                addstr(&self.cursor_char());
+                attroff(COLOR_PAIR(HIGHLIGHT_PAIR));
+            },
+            CommandType::Find => {
+                let prefix = format!("Find ({}, tab to change): ", self.find_mode.label());
+                let str = format!("{}{}", prefix, self.input_value);
+                addstr(&str as &str);
+
+                mv(height - 1, 3 + prefix.len() as i32 + self.input_cursor);
+                attron(COLOR_PAIR(HIGHLIGHT_PAIR));
+                addstr(&self.cursor_char());
+                attroff(COLOR_PAIR(HIGHLIGHT_PAIR));
+            },
+            CommandType::Compress => {
+                let prefix = format!("Archive name (.{}, tab to change): ", self.archive_format.extension());
+                let str = format!("{}{}", prefix, self.input_value);
+                addstr(&str as &str);
+
+                mv(height - 1, 3 + prefix.len() as i32 + self.input_cursor);
+                attron(COLOR_PAIR(HIGHLIGHT_PAIR));
+                addstr(&self.cursor_char());
+                attroff(COLOR_PAIR(HIGHLIGHT_PAIR));
+            },
+            CommandType::FindResults => {
+                let str = format!("{} results: {} go to, ESC close", self.find.len(), self.keymap.key_label(keys::Action::Open));
+                addstr(&str as &str);
+            },
+            CommandType::Columns => {
+                addstr(&self.columns.label());
+            },
+            CommandType::NewBookmark => {
+                let str = format!("Bookmark name: {}", self.input_value);
                 addstr(&str as &str);
 
-                let cursor = self.input_cursor as usize;
                 mv(height - 1, 18 + self.input_cursor);
                 attron(COLOR_PAIR(HIGHLIGHT_PAIR));
-                addstr(self.input_value.get(cursor..=cursor).unwrap_or(" "));
+                addstr(&self.cursor_char());
                 attroff(COLOR_PAIR(HIGHLIGHT_PAIR));
             },
+            CommandType::Mark => {
+                addstr("Mark: press a letter");
+            },
+            CommandType::JumpToMark => {
+                addstr("Jump to mark: press a letter");
+            },
+            CommandType::Recent => {
+                addstr(&format!("Recent folders: {} go to, ESC close", self.keymap.key_label(keys::Action::Open)));
+            },
+            CommandType::Bookmarks => {
+                addstr(&format!("Bookmarks: {} go to, {} add this folder, {} delete, ESC close",
+                    self.keymap.key_label(keys::Action::Open), self.keymap.key_label(keys::Action::Mark), self.keymap.key_label(keys::Action::Delete)));
+            },
+            CommandType::CopyToTab => {
+                addstr("Copy to tab: press its number");
+            },
+            CommandType::MoveToTab => {
+                addstr("Move to tab: press its number");
+            },
             CommandType::Delete => {
-                addstr("Press enter to delete");
+                addstr("Press enter to move to trash");
+            },
+            CommandType::PermanentDelete => {
+                addstr("Press enter to delete permanently");
+            },
+            CommandType::Trash => {
+                addstr(&format!("Trash: {} restore, {} purge, ESC close",
+                    self.keymap.key_label(keys::Action::Open), self.keymap.key_label(keys::Action::Delete)));
+            },
+            CommandType::Sync => {
+                let str = format!("{} differences: {} apply, {} apply all, ESC close",
+                    self.sync.len(), self.keymap.key_label(keys::Action::Open), self.keymap.key_label(keys::Action::Sync));
+                addstr(&str as &str);
             },
             CommandType::Error(err) => {
-                addstr(&err);
+                attron(COLOR_PAIR(ERROR_PAIR));
+                addstr(err);
+                attroff(COLOR_PAIR(ERROR_PAIR));
             },
             CommandType::None => {
-                let bottom = format!("height: {} width: {}", height.to_string(), width.to_string());
+                let mut bottom = format!("height: {} width: {}", height, width);
+                bottom.push_str(&format!(" | sort: {}", self.sort_order().label()));
+                if !self.keymap.pending().is_empty() {
+                    bottom.push_str(&format!(" | {}", self.keymap.pending()));
+                }
+                if !self.clipboard.is_empty() {
+                    bottom.push_str(&format!(" | {} {}", self.clipboard.len(), if self.clipboard_cut { "cut" } else { "yanked" }));
+                }
                 addstr(&bottom as &str);
             }
         }
 
         mv(0, 0);
+        match self.command {
+            CommandType::Trash => { addstr("Trash"); },
+            CommandType::Sync => { addstr(&format!("Sync {}", self.curr_path)); },
+            CommandType::FindResults => { addstr("Find"); },
+            CommandType::Bookmarks => { addstr("Bookmarks"); },
+            CommandType::Recent => { addstr("Recent"); },
+            _ => self.draw_tab_bar(),
+        }
+    }
+
+    // Names left after an ambiguous tab completion, shown above the prompt
+    fn draw_completions(&self, width: &i32, height: &i32) {
+        if !self.completions.is_empty() {
+            mv(height - 2, 3);
+            addstr(&self.completions.join("  ").chars().take(max(0, width - 4) as usize).collect::<String>());
+        }
+    }
+
+    fn draw_tab_bar(&self) {
+        if self.tabs.len() > 1 {
+            for (i, tab) in self.tabs.iter().enumerate() {
+                // The active tab's path lives in curr_path until it is switched away from
+                let path = if i == self.tab_curr { &self.curr_path } else { &tab.curr_path };
+                let pair = if i == self.tab_curr { HIGHLIGHT_PAIR } else { REGULAR_PAIR };
+
+                attron(COLOR_PAIR(pair));
+                addstr(&format!(" {} {} ", i + 1, tabs::label(path)));
+                attroff(COLOR_PAIR(pair));
+                addstr(" ");
+            }
+        }
+
         addstr(&self.curr_path);
     }
 
-    fn list_item(&mut self, label: &str, color_pair: i16, row: &i32) {
+    fn list_item(&mut self, label: &str, color_pair: i16, row: &i32, left: i32, matches: &[usize]) {
             attron(COLOR_PAIR(color_pair));
             let idx = row + 1;
 
-            mv(idx as i32, 1);
-            addstr(label);
+            mv(idx, left + 1);
+            for (i, c) in label.chars().enumerate() {
+                if matches.contains(&i) {
+                    attron(A_BOLD() | A_UNDERLINE());
+                    addstr(&c.to_string());
+                    attroff(A_BOLD() | A_UNDERLINE());
+                } else {
+                    addstr(&c.to_string());
+                }
+            }
             attroff(COLOR_PAIR(color_pair));
     }
 
+    // The typed character under the cursor, counted in characters rather than bytes
+    fn cursor_char(&self) -> String {
+        self.input_value.chars().nth(self.input_cursor as usize).map(String::from).unwrap_or_else(|| String::from(" "))
+    }
+
     fn set_parent_path(&mut self) {
         let parent = Path::new(&self.curr_path);
 
@@ -102,146 +335,164 @@ impl Ui {
     }
 
     fn handle_input(&mut self, c: &i32, entries: &mut Vec<tree::Entry>, file_curr: &usize, start_select: &Option<i32>) {
-        match self.command {
-            CommandType::Error(_) => {
-                self.command = CommandType::None;
-                self.input_value = String::from("");
-                self.input_cursor = 0;
-            },
-            _ => {}
+        // Any key dismisses an error
+        if let CommandType::Error(_) = self.command {
+            self.command = CommandType::None;
+            self.input_value = String::from("");
+            self.input_cursor = 0;
+            return;
         }
 
+        self.completions.clear();
+
         match c {
-            127 => if self.input_value.len() != 0 { // BACKSPACE
-                self.input_value.remove(self.input_value.len() - 1);
+            127 if !self.input_value.is_empty() => { // BACKSPACE
+                self.input_value.pop();
                 self.input_cursor -= 1;
-            }, 
+            },
             32..=126 => {
                 self.input_value.push(*c as u8 as char);
                 self.input_cursor += 1;
             },
+            9 => match self.command { // TAB
+                CommandType::Find => self.find_mode = self.find_mode.next(),
+                CommandType::Compress => self.archive_format = self.archive_format.next(),
+                CommandType::GoTo | CommandType::NewLink => {
+                    let (value, completions) = complete::complete(&self.input_value, &self.curr_path, self.backend.as_ref());
+                    self.input_value = value;
+                    self.input_cursor = self.input_value.chars().count() as i32;
+                    self.completions = completions;
+                },
+                _ => {}
+            },
             10 => { // ENTER
                 match self.command {
-                    CommandType::NewFile => {
-                        let path = { 
-                            if self.curr_path == "/" { 
-                                format!("/{}", self.input_value) 
-                            } else { 
-                                format!("{}/{}", self.resolve_path(&String::from(&self.curr_path)).0, self.input_value) 
-                            }  
+                    CommandType::Find => {
+                        let found = match self.backend.tree() {
+                            Some(tree) => tree.find(&self.input_value, &self.find_mode),
+                            None => Err(String::from("Find searches the tree, it isn't available with --real")),
                         };
 
-                        let _path = Path::new(&path);
-                        if _path.exists() && !_path.is_dir() {
-                            self.command = CommandType::Error(String::from("File already exists"));
-                        } else {
-                            match File::create(&path) {
-                                Ok(_) => {
-                                    let new_entry = tree::Entry {
-                                        name: self.input_value.clone(),
-                                        path: path.clone(),
-                                        r#type: String::from("f"),
-                                    };
-                                    entries.insert(0, new_entry.clone());
-                                    self.add_entry(new_entry, true);
-                                    self.command = CommandType::None;
-                                },
-                                Err(err) => self.command = CommandType::Error(err.to_string())
-                            }
+                        match found {
+                            Ok(results) => {
+                                self.find = results;
+                                self.find_curr = 0;
+                                self.command = CommandType::FindResults;
+                            },
+                            Err(err) => self.command = CommandType::Error(err)
                         }
                     },
-                    CommandType::NewDir => {
-                        let path = { 
-                            if self.curr_path == "/" { 
-                                format!("/{}", self.input_value) 
-                            } else { 
-                                format!("{}/{}", self.resolve_path(&String::from(&self.curr_path)).0, self.input_value) 
-                            }  
+                    CommandType::NewFile | CommandType::NewDir => {
+                        let path = match self.resolve_path(&self.curr_path) {
+                            Ok((dir, prefix)) => format!("{}{}{}", dir, prefix, self.input_value),
+                            Err(err) => {
+                                self.command = CommandType::Error(err.to_string());
+                                return;
+                            }
                         };
 
-                        match create_dir(&path) {
-                            Ok(_) => {
-                                let new_entry = tree::Entry {
-                                    name: self.input_value.clone(),
-                                    path: path.clone(),
-                                    r#type: String::from("d"),
-                                };
-                                entries.insert(0, new_entry.clone());
-                                self.add_entry(new_entry, true);
+                        let created = match self.command {
+                            CommandType::NewDir => self.backend.create_dir(&path),
+                            _ => self.backend.create_file(&path),
+                        };
+
+                        match created {
+                            Ok(new_entry) => {
+                                entries.insert(0, new_entry);
+                                self.metadata.clear();
+                                self.update_json();
+                                self.record_change();
                                 self.command = CommandType::None;
                             },
-                            Err(err) => self.command = CommandType::Error(err.to_string())
+                            Err(err) => self.command = CommandType::Error(err)
                         }
                     },
+                    CommandType::NewLink if self.input_value.is_empty() => {
+                        self.command = CommandType::Error(String::from("Invalid path"));
+                    },
                     CommandType::NewLink => {
-                        let path = Path::new(&self.input_value);
-                        let mut found: Option<&tree::Entry> = None;
-
-                        for e in self.tree.root.iter() {
-                            if e.path == path.display().to_string() {
-                                found = Some(e);
-                                break
+                        // Links are made to the real path, typed paths can go through other links
+                        let target_path = complete::absolute(&self.input_value, &self.curr_path);
+                        let target = match self.backend.resolve(&target_path) {
+                            Ok(target) => target,
+                            Err(err) => {
+                                self.command = CommandType::Error(err.to_string());
+                                return;
                             }
-                        }
-
-                        if found.is_none() {
-                            panic!("Path new found");
                         };
 
-                        
-                        entries.insert(0, tree::Entry {
-                            r#type: format!("{}", found.unwrap().r#type),
-                            //path:&Path::new(&format!("{}/{}", self.curr_path, path.file_name().unwrap().to_str().unwrap())).display().to_string(), 
-                            name: String::from(path.file_name().unwrap().to_str().unwrap()),
-                            path: path.display().to_string(), 
-                        });
+                        let name = tree::file_name(&target_path);
+                        let dir = self.link_dir();
+                        let path = format!("{}{}{}", dir, if dir == "/" { "" } else { "/" }, name);
 
-                        self.add_link(tree::Link {
-                            path: Path::new(&format!("{}/{}", self.curr_path, path.file_name().unwrap().to_str().unwrap())).display().to_string(), 
-                            r#type: format!("{}", found.unwrap().r#type),
-                            name: String::from(path.file_name().unwrap().to_str().unwrap()),
-                            //path: _path.display().to_string(), 
-                            link_path: path.display().to_string(), 
+                        match self.backend.link(&target, &path) {
+                            Ok(_) => {
+                                self.update_json();
+                                self.record_change();
+                                self.reload_entries(entries);
+                                self.command = CommandType::None;
+                            },
+                            Err(err) => self.command = CommandType::Error(err)
+                        }
+                    },
+                    CommandType::Delete | CommandType::PermanentDelete => {
+                        let permanent = matches!(self.command, CommandType::PermanentDelete);
+                        self.command = CommandType::None;
 
-                        }, true);
+                        for entry in selected_entries(entries, *file_curr, start_select) {
+                            // Permanently deleted entries still go to the journal's staging area until it is pruned
+                            let deleted = if permanent {
+                                self.journal.stage_path().map_err(|err| err.to_string()).and_then(|staged| self.backend.delete(&entry.path, &staged))
+                            } else {
+                                self.backend.trash(&entry.path)
+                            };
 
-                        self.command = CommandType::None;
-                    },
-                    CommandType::Delete => {
-                        fn delete_entry(entry: &tree::Entry, command: &mut CommandType) {
-                            if &entry.r#type == "d" {
-                                match remove_dir_all(&entry.path) {
-                                    Ok(_) => {
-                                    },
-                                    Err(err) => *command = CommandType::Error(err.to_string())
-                                }
-                            } else if &entry.r#type == "f" {
-                                match remove_file(&entry.path) {
-                                    Ok(_) => {
-                                    },
-                                    Err(err) => *command = CommandType::Error(err.to_string())
-                                }
+                            if let Err(err) = deleted {
+                                self.command = CommandType::Error(err);
                             }
                         }
 
-                        match *start_select {
-                            None => {
-                                delete_entry(&entries[*file_curr], &mut self.command);
-                                self.delete_entry(&entries[*file_curr].path, &entries[*file_curr].r#type == "d", true);
-                            }
-                            start => {
-                                let diff: i32 = start.unwrap() - *file_curr as i32; 
-                                for i in 0..=diff.abs() {
-                                    let idx: i32 = i + min(start.unwrap(), *file_curr as i32);
-                                    delete_entry(&entries[idx as usize], &mut self.command);
-                                    self.delete_entry(&entries[idx as usize].path, &entries[idx as usize].r#type == "d", true);
-                                }
+                        self.update_json();
+                        self.record_change();
+                        self.reload_entries(entries);
+                    },
+                    CommandType::NewBookmark => {
+                        if self.input_value.is_empty() {
+                            self.command = CommandType::Error(String::from("Invalid name"));
+                        } else {
+                            self.bookmarks.add(&self.input_value, &self.curr_path);
+                            self.save_bookmarks();
+                            if let CommandType::NewBookmark = self.command {
+                                self.command = CommandType::Bookmarks;
                             }
                         }
+                    },
+                    CommandType::Compress => {
+                        let selected = selected_entries(entries, *file_curr, start_select);
+                        self.compress(&selected, entries);
+                    },
+                    CommandType::Rename => {
+                        let entry = entries[*file_curr].clone();
+                        let path = match Path::new(&entry.path).parent() {
+                            Some(parent) => format!("{}/{}", parent.display().to_string().trim_end_matches('/'), self.input_value),
+                            None => format!("/{}", self.input_value),
+                        };
 
-                        self.set_entries(entries);
-                        self.command = CommandType::None;
-
+                        if self.input_value.is_empty() || self.input_value.contains('/') {
+                            self.command = CommandType::Error(String::from("Invalid name"));
+                        } else if path == entry.path {
+                            self.command = CommandType::None;
+                        } else {
+                            match self.backend.rename(&entry.path, &path) {
+                                Ok(_) => {
+                                    self.update_json();
+                                    self.record_change();
+                                    self.reload_entries(entries);
+                                    self.command = CommandType::None;
+                                },
+                                Err(err) => self.command = CommandType::Error(err)
+                            }
+                        }
                     },
                     _ => {}
                 }
@@ -253,160 +504,727 @@ impl Ui {
         }
     }
 
-    fn resolve_path(&mut self, path: &str) -> (String, String) {
-        if path == "/" {
-            return (path.to_string(), "".to_string());
-        } else {
-            let mut found_flag = false;
+    fn compress(&mut self, selected: &[tree::Entry], entries: &mut Vec<tree::Entry>) {
+        if self.input_value.is_empty() || self.input_value.contains('/') {
+            self.command = CommandType::Error(String::from("Invalid name"));
+            return;
+        }
 
-            for e in self.tree.root.iter() {
-                if e.path == path {
-                    found_flag = true;
+        let path = match self.resolve_path(&self.curr_path) {
+            Ok((dir, prefix)) => format!("{}{}{}.{}", dir, prefix, self.input_value, self.archive_format.extension()),
+            Err(err) => {
+                self.command = CommandType::Error(err.to_string());
+                return;
+            }
+        };
+
+        let mut sources: Vec<(String, PathBuf)> = vec![];
+        for entry in selected {
+            match self.resolve_path(&entry.path) {
+                Ok((real_path, _)) => sources.push((entry.name.clone(), PathBuf::from(real_path))),
+                Err(err) => {
+                    self.command = CommandType::Error(err.to_string());
+                    return;
                 }
             }
+        }
 
-            if found_flag {
-                return (path.to_string(), "/".to_string());
+        let mut max_x: i32 = 0;
+        let mut max_y: i32 = 0;
+        getmaxyx(stdscr(), &mut max_y, &mut max_x);
+
+        // Only redrawn when the percentage changes, there can be many small files
+        let mut shown: Option<u64> = None;
+        let created = self.backend.compress(&path, self.archive_format, &sources, &mut |done, total| {
+            let percent = (done * 100).checked_div(total).unwrap_or(100);
+            if shown != Some(percent) {
+                shown = Some(percent);
+                mv(max_y - 1, 0);
+                clrtoeol();
+                mv(max_y - 1, 3);
+                addstr(&format!("Compressing {}% ({} of {})", percent, columns::human_size(done), columns::human_size(total)));
+                refresh();
             }
+        });
 
-            let mut split: Vec<&str> = path.split("/").collect();
-            let mut real_path = "".to_string();
+        match created {
+            Ok(_) => {
+                self.update_json();
+                self.record_change();
+                self.command = CommandType::None;
+            },
+            Err(err) => self.command = CommandType::Error(err),
+        }
 
-            split.remove(0);
-            while split.len() != 0 {
-                let mut found = false;
-                let new_path = format!("{}/{}", real_path, split[0]);
-                split.remove(0);
+        self.reload_entries(entries);
+    }
 
-                for e in self.tree.root.iter() {
-                    if  e.path == new_path {
-                        found = true;
-                        break;
-                    }
+    // The entry `path` is listed as in its parent folder
+    fn listed_entry(&self, path: &str) -> Option<tree::Entry> {
+        let name = tree::file_name(path);
+        self.backend.list(tree::parent_path(path)).unwrap_or_default().into_iter().find(|e| e.name == name)
+    }
+
+    // The best ranked visited folder for the query. Folders that were never visited are
+    // looked up in the tree, directories and links alike, the shortest path wins.
+    fn jump_target(&mut self) -> Option<String> {
+        let query = self.input_value.clone();
+        self.command = CommandType::None;
+        self.input_value = String::from("");
+        self.input_cursor = 0;
+
+        let words = frecency::query_words(&query);
+        if words.is_empty() {
+            self.command = CommandType::Error(String::from("Invalid query"));
+            return None;
+        }
+
+        // Folders that can't be listed right now are skipped, they may come back
+        for path in self.frecency.ranked(&query) {
+            if let Some(e) = self.listed_entry(&path) {
+                if e.r#type == "d" || e.r#type == "dl" {
+                    return Some(path);
                 }
+            }
+        }
 
-                if found {
-                    real_path = new_path;
-                } else {
-                    for l in self.tree._links.iter() {
-                        if  l.path == new_path {
-                            real_path = String::from(&l.link_path); 
-                            found = true;
-                            break;
-                        }
+        let last = words.last().unwrap();
+        let in_tree = self.backend.tree().map(|tree| tree.find(last, &find::FindMode::Name).unwrap_or_default()).unwrap_or_default();
+        let mut found: Vec<tree::Entry> = in_tree.into_iter()
+            .filter(|e| (e.r#type == "d" || e.r#type == "dl") && frecency::matches(&e.path, &words))
+            .collect();
+        found.sort_by(|a, b| a.path.len().cmp(&b.path.len()).then_with(|| a.path.cmp(&b.path)));
+
+        match found.into_iter().next() {
+            Some(e) => Some(e.path),
+            None => {
+                self.command = CommandType::Error(format!("No folder matches \"{}\"", query));
+                None
+            },
+        }
+    }
+
+    // Whether `path` can still be opened as a folder
+    fn is_dir_path(&self, path: &str) -> bool {
+        match self.listed_entry(path) {
+            Some(e) => e.r#type == "d" || e.r#type == "dl" || archive::is_archive(&e.name),
+            None => path == "/",
+        }
+    }
+
+    // Takes over the settings of the last session and, unless a start path was given, its
+    // tabs. Returns the tab to show, tabs whose folder is gone are left out.
+    fn restore(&mut self, session: session::Session, keep_tabs: bool) -> Option<tabs::Tab> {
+        self.sort_orders = session.sort_orders;
+        self.columns = session.columns;
+        self.preview = session.preview;
+        self.search = session.search;
+        self.find_mode = session.find_mode;
+
+        if !keep_tabs {
+            return None;
+        }
+
+        let active = session.tabs.get(session.tab_curr).map(|tab| tab.curr_path.clone());
+        let tabs: Vec<tabs::Tab> = session.tabs.into_iter().filter(|tab| self.is_dir_path(&tab.curr_path)).collect();
+        if tabs.is_empty() {
+            return None;
+        }
+
+        self.tab_curr = tabs.iter().position(|tab| Some(&tab.curr_path) == active.as_ref()).unwrap_or(0);
+        self.tabs = tabs;
+        self.other_pane = session.other_pane.filter(|pane| self.is_dir_path(&pane.curr_path));
+        self.history = history::History::new(&self.tabs[self.tab_curr].curr_path);
+
+        Some(self.tabs[self.tab_curr].clone())
+    }
+
+    fn session(&self, active: tabs::Tab) -> session::Session {
+        let mut tabs = self.tabs.clone();
+        tabs[self.tab_curr] = active;
+
+        session::Session {
+            tabs,
+            tab_curr: self.tab_curr,
+            other_pane: self.other_pane.clone(),
+            sort_orders: self.sort_orders.clone(),
+            columns: self.columns.clone(),
+            preview: self.preview,
+            search: self.search.clone(),
+            find_mode: self.find_mode,
+        }
+    }
+
+    // Checks the typed path and returns it as an absolute path to a folder
+    fn go_to_target(&mut self) -> Option<String> {
+        let path = complete::absolute(&self.input_value, &self.curr_path);
+        self.command = CommandType::None;
+        self.input_value = String::from("");
+        self.input_cursor = 0;
+        self.completions.clear();
+
+        if path == "/" {
+            return Some(path);
+        }
+
+        match self.listed_entry(&path) {
+            Some(e) if e.r#type == "d" || e.r#type == "dl" || archive::is_archive(&e.name) => Some(path),
+            Some(_) => {
+                self.command = CommandType::Error(format!("{} is not a folder", path));
+                None
+            },
+            None => {
+                self.command = CommandType::Error(format!("{} not found", path));
+                None
+            },
+        }
+    }
+
+    fn resolve_path(&self, path: &str) -> Result<(String, String), tree::ResolveError> {
+        let real_path = self.backend.resolve(path)?;
+        let prefix = if real_path == "/" { "" } else { "/" };
+
+        Ok((real_path, prefix.to_string()))
+    }
+
+    fn set_entries(&mut self, entries: &mut Vec<tree::Entry>) {
+        *entries = vec![];
+
+        match self.list_dir(&self.curr_path) {
+            Ok(listed) => *entries = listed,
+            Err(err) => self.command = CommandType::Error(err)
+        }
+    }
+
+    // Lists the folder again after something changed, cached metadata may be stale anywhere
+    fn reload_entries(&mut self, entries: &mut Vec<tree::Entry>) {
+        self.metadata.clear();
+        self.set_entries(entries);
+    }
+
+    fn list_dir(&self, path: &str) -> Result<Vec<tree::Entry>, String> {
+        let mut entries = self.backend.list(path)?;
+        let order = self.sort_orders.get(path).copied().unwrap_or_else(sort::SortOrder::new);
+
+        sort::sort_entries(&mut entries, &order, self.backend.as_ref());
+        Ok(entries)
+    }
+
+    fn sort_order(&self) -> sort::SortOrder {
+        self.sort_orders.get(&self.curr_path).copied().unwrap_or_else(sort::SortOrder::new)
+    }
+
+    // New links go to the other pane when there is one
+    fn link_dir(&self) -> String {
+        match &self.other_pane {
+            Some(pane) => pane.curr_path.clone(),
+            None => self.curr_path.clone(),
+        }
+    }
+
+    fn draw_entries(&mut self, entries: &[tree::Entry], pane: &tabs::Tab, left: i32, width: i32, max_y: i32) {
+        for (i, entry) in entries.iter().enumerate() {
+            if i >= pane.top_offset.try_into().unwrap() && (i as i32) - pane.top_offset < max_y - 2 {
+                let mut pair = { 
+                    if pane.file_curr == i {
+                        HIGHLIGHT_PAIR
+                    } else if entry.r#type == "bl" || entry.r#type == "cl" {
+                        ERROR_PAIR
+                    } else if entry.r#type.ends_with('l') {
+                        LINK_PAIR
+                    } else if entry.r#type == "d" {
+                        DIR_PAIR
+                    } else {
+                        FILE_PAIR
                     }
+                };
 
-                    if !found {
-                        panic!("not found {}", real_path);
+                if let Some(start) = pane.select_start {
+                    if (start <= i as i32 && pane.file_curr as i32 >= i as i32) || (start >= i as i32 && pane.file_curr as i32 <= i as i32) {
+                        pair = HIGHLIGHT_PAIR;
                     }
                 }
 
+                let mut label = format!("{} {}", entry.r#type, &entry.name);
+                if self.columns.any() {
+                    let info = self.metadata.info(&pane.curr_path, entry, self.backend.as_ref());
+                    let cols = columns::format_columns(info, &self.columns);
+                    label = columns::layout_row(&label, &cols, max(0, width - 2) as usize);
+                } else {
+                    label = label.chars().take(max(0, width - 2) as usize).collect();
+                }
+                let matches: Vec<usize> = {
+                    if self.search.is_empty() {
+                        vec![]
+                    } else {
+                        let offset = entry.r#type.chars().count() + 1;
+                        fuzzy::fuzzy_match(&self.search, &entry.name).unwrap_or_default().iter().map(|i| i + offset).collect()
+                    }
+                };
+                self.list_item(&label, pair, &((i as i32) - pane.top_offset), left, &matches);
             }
+        }
+    }
+
+    // The pane that isn't active, listed again on every draw so it never goes stale
+    fn draw_pane(&mut self, pane: &tabs::Tab, left: i32, width: i32, max_y: i32) {
+        let entries = self.list_dir(&pane.curr_path).unwrap_or_default();
+        let mut pane = pane.clone();
+        pane.file_curr = min(pane.file_curr, entries.len().saturating_sub(1));
+        scroll_into_view(&pane.file_curr, &mut pane.top_offset, &max_y);
 
-            return (real_path, "/".to_string());
+        for row in 1..max_y - 1 {
+            mv(row, left - 1);
+            addstr("|");
         }
 
+        mv(0, left);
+        addstr(&pane.curr_path);
+        self.draw_entries(&entries, &pane, left, width, max_y);
     }
 
-    fn set_entries(&mut self, entries: &mut Vec<tree::Entry>) {
-        *entries = vec![];
-        
-        let (path, prefix) = self.resolve_path(&String::from(&self.curr_path));
-        self.get_entries_with_path(entries, &path, &prefix);
-    }
-
-    fn get_entries_with_path(&self, entries: &mut Vec<tree::Entry>, path: &str, join: &str) {
-            for e in self.tree._links.iter() {
-                let mut x: String = path.to_owned();
-                x.push_str(join);
-                x.push_str(&*e.name);
-
-                if x == *e.path.to_string() {
-                    entries.push(tree::Entry{
-                        name: (*e.name).to_string(),
-                        path: (*e.path).to_string(),
-                        r#type: format!("{}l", (*e.r#type).to_string()),
-                    });
-                }
+    fn set_sort_order(&mut self, order: sort::SortOrder, entries: &mut Vec<tree::Entry>) {
+        self.sort_orders.insert(self.curr_path.clone(), order);
+        self.set_entries(entries);
+    }
+
+    fn set_clipboard(&mut self, selected: Vec<tree::Entry>, cut: bool) {
+        self.clipboard = selected;
+        self.clipboard_cut = cut;
+    }
+
+    fn paste(&mut self, entries: &mut Vec<tree::Entry>) {
+        let (items, cut) = (self.clipboard.clone(), self.clipboard_cut);
+        let failed = self.paste_into(&self.curr_path.clone(), &items, cut);
+
+        // What couldn't be moved stays cut so it can be pasted somewhere else
+        if cut {
+            self.clipboard = failed;
+        }
+
+        self.reload_entries(entries);
+    }
+
+    fn copy_to_tab(&mut self, tab: usize, selected: &[tree::Entry], cut: bool, entries: &mut Vec<tree::Entry>) {
+        if tab >= self.tabs.len() || tab == self.tab_curr {
+            self.command = CommandType::Error(format!("There is no other tab {}", tab + 1));
+            return;
+        }
+
+        self.copy_to_dir(&self.tabs[tab].curr_path.clone(), selected, cut, entries);
+    }
+
+    fn copy_to_dir(&mut self, dir_path: &str, selected: &[tree::Entry], cut: bool, entries: &mut Vec<tree::Entry>) {
+        self.paste_into(dir_path, selected, cut);
+        self.reload_entries(entries);
+    }
+
+    // Copies or moves `items` into the directory shown at `dir_path`, returns the ones that failed
+    fn paste_into(&mut self, dir_path: &str, items: &[tree::Entry], cut: bool) -> Vec<tree::Entry> {
+        let dir = match self.resolve_path(dir_path) {
+            Ok((dir, _)) => dir,
+            Err(err) => {
+                self.command = CommandType::Error(err.to_string());
+                return items.to_vec();
             }
+        };
+        let mut failed: Vec<tree::Entry> = vec![];
 
-            for e in self.tree.root.iter() {
-                let mut x: String = path.to_owned();
-                x.push_str(join);
-                x.push_str(&*e.name);
-
-                if x == *e.path.to_string() {
-                    entries.push(tree::Entry{
-                        name: (*e.name).to_string(),
-                        path: (*e.path).to_string(),
-                        r#type: (*e.r#type).to_string(),
-                    });
+        for entry in items.iter() {
+            let path = {
+                if dir == "/" {
+                    format!("/{}", entry.name)
+                } else {
+                    format!("{}/{}", dir, entry.name)
                 }
+            };
+
+            if path == entry.path || path.starts_with(&format!("{}/", entry.path)) {
+                self.command = CommandType::Error(String::from("Cannot paste into itself"));
+                failed.push(entry.clone());
+                continue;
+            }
+
+            if self.backend.exists(&path) {
+                self.command = CommandType::Error(format!("{} already exists", entry.name));
+                failed.push(entry.clone());
+                continue;
             }
+
+            let pasted = if cut {
+                self.backend.rename(&entry.path, &path)
+            } else {
+                self.backend.copy(&entry.path, &path)
+            };
+
+            if let Err(err) = pasted {
+                self.command = CommandType::Error(err);
+                failed.push(entry.clone());
+            }
+        }
+
+        self.record_change();
+        self.update_json();
+        failed
     }
 
-    fn add_entry(&mut self, entry: tree::Entry, update_json: bool) {
-        self.tree.root.insert(0, entry);
+    // What the backend did since the last time it was asked
+    fn take_change(&mut self) -> (Vec<journal::DiskOp>, Vec<journal::TreeOp>) {
+        let ops = self.backend.take_ops();
+        let tree_ops = self.backend.tree().map(|tree| tree.take_tree_ops()).unwrap_or_default();
+        (ops, tree_ops)
+    }
 
-        if update_json {
-            self.update_json();
+    fn record_change(&mut self) {
+        let (ops, tree_ops) = self.take_change();
+        self.journal.record(ops, tree_ops);
+    }
+
+    fn undo(&mut self, entries: &mut Vec<tree::Entry>) {
+        match self.journal.undo(self.backend.tree()) {
+            Ok(true) => {
+                self.update_json();
+                self.reload_entries(entries);
+            },
+            Ok(false) => self.command = CommandType::Error(String::from("Already at oldest change")),
+            Err(err) => self.command = CommandType::Error(format!("Couldn't undo the change, it was dropped: {}", err))
         }
     }
 
-    fn add_link(&mut self, link: tree::Link, update_json: bool) {
-        self.tree._links.insert(0, link);
+    fn redo(&mut self, entries: &mut Vec<tree::Entry>) {
+        match self.journal.redo(self.backend.tree()) {
+            Ok(true) => {
+                self.update_json();
+                self.reload_entries(entries);
+            },
+            Ok(false) => self.command = CommandType::Error(String::from("Already at newest change")),
+            Err(err) => self.command = CommandType::Error(format!("Couldn't redo the change, it was dropped: {}", err))
+        }
+    }
 
-        if update_json {
-            self.update_json();
+    fn open_trash(&mut self) {
+        match self.backend.trash_items() {
+            Ok(items) => {
+                self.trash = items;
+                self.trash_curr = 0;
+                self.command = CommandType::Trash;
+            },
+            Err(err) => self.command = CommandType::Error(err)
         }
     }
 
-    fn delete_entry(&mut self, entry_path: &str, delete_children: bool, update_json: bool) {
+    fn draw_popup(&mut self, labels: &[String], curr: usize, max_y: &i32) {
+        let top = max(0, curr as i32 - (max_y - 3));
 
-        let mut found_flag = false;
+        for (i, label) in labels.iter().enumerate().skip(top as usize) {
+            if (i as i32) - top >= max_y - 2 {
+                break;
+            }
 
-        let mut i = 0;
+            self.list_item(label, if i == curr { HIGHLIGHT_PAIR } else { REGULAR_PAIR }, &((i as i32) - top), 0, &[]);
+        }
+    }
 
-        while i < self.tree._links.len() {
-            if self.tree._links[i].link_path.starts_with(&format!("{}/", entry_path)) || self.tree._links[i].link_path == entry_path {
-                self.tree._links.remove(i);
-            } else if self.tree._links[i].path == entry_path {
-                self.tree._links.remove(i);
-                found_flag = true;
-            } else {
-                i += 1;
+    fn handle_trash_input(&mut self, c: &i32, entries: &mut Vec<tree::Entry>) {
+        match self.keymap.feed(*c) {
+            Some(keys::Action::Up) => self.trash_curr = self.trash_curr.saturating_sub(1),
+            Some(keys::Action::Down) => self.trash_curr = min(self.trash_curr + 1, self.trash.len().saturating_sub(1)),
+            Some(keys::Action::Quit) => self.command = CommandType::None,
+            Some(keys::Action::Open | keys::Action::Delete) if self.read_only => self.command = CommandType::Error(String::from("Read-only mode")),
+            Some(keys::Action::Open) if !self.trash.is_empty() => {
+                let item = self.trash.remove(self.trash_curr);
+
+                if let Err(err) = self.backend.restore(&item.path, &item.name) {
+                    self.command = CommandType::Error(err);
+                    return;
+                }
+
+                self.record_change();
+                self.update_json();
+                self.reload_entries(entries);
+                self.trash_curr = min(self.trash_curr, self.trash.len().saturating_sub(1));
+            },
+            Some(keys::Action::Delete) if !self.trash.is_empty() => {
+                let item = self.trash.remove(self.trash_curr);
+
+                if let Err(err) = self.backend.purge(&item.name) {
+                    self.command = CommandType::Error(err);
+                    return;
+                }
+
+                self.update_json();
+                self.trash_curr = min(self.trash_curr, self.trash.len().saturating_sub(1));
+            },
+            _ => {}
+        }
+    }
+
+    fn save_bookmarks(&mut self) {
+        if let Err(err) = self.bookmarks.save() {
+            self.command = CommandType::Error(err);
+        }
+    }
+
+    fn set_mark(&mut self, c: i32) {
+        self.command = CommandType::None;
+
+        if (c as u8 as char).is_ascii_alphabetic() {
+            self.bookmarks.set_mark(c as u8 as char, &self.curr_path.clone());
+            self.save_bookmarks();
+        }
+    }
+
+    // Returns the folder to go to once a bookmark is picked
+    fn handle_bookmarks_input(&mut self, c: &i32) -> Option<String> {
+        let list = self.bookmarks.list();
+
+        match self.keymap.feed(*c) {
+            Some(keys::Action::Up) => self.bookmarks_curr = self.bookmarks_curr.saturating_sub(1),
+            Some(keys::Action::Down) => self.bookmarks_curr = min(self.bookmarks_curr + 1, list.len().saturating_sub(1)),
+            Some(keys::Action::Quit) => self.command = CommandType::None,
+            Some(keys::Action::Mark) => {
+                self.command = CommandType::NewBookmark;
+                self.input_value = tabs::label(&self.curr_path);
+                self.input_cursor = self.input_value.chars().count() as i32;
+            },
+            Some(keys::Action::Delete) if !list.is_empty() => {
+                self.bookmarks.remove(self.bookmarks_curr);
+                self.bookmarks_curr = min(self.bookmarks_curr, list.len().saturating_sub(2));
+                self.save_bookmarks();
+            },
+            Some(keys::Action::Open) if !list.is_empty() => {
+                self.command = CommandType::None;
+                return Some(list[self.bookmarks_curr].path.clone());
+            },
+            _ => {}
+        }
+
+        None
+    }
+
+    // Returns the folder to go to once one is picked
+    fn handle_recent_input(&mut self, c: &i32) -> Option<String> {
+        match self.keymap.feed(*c) {
+            Some(keys::Action::Up) => self.recent_curr = self.recent_curr.saturating_sub(1),
+            Some(keys::Action::Down) => self.recent_curr = min(self.recent_curr + 1, self.recent.len().saturating_sub(1)),
+            Some(keys::Action::Quit) => self.command = CommandType::None,
+            Some(keys::Action::Open) if !self.recent.is_empty() => {
+                self.command = CommandType::None;
+                return Some(self.recent[self.recent_curr].clone());
+            },
+            _ => {}
+        }
+
+        None
+    }
+
+    fn open_sync(&mut self) {
+        let dir = match self.resolve_path(&self.curr_path) {
+            Ok((dir, _)) => dir,
+            Err(err) => {
+                self.command = CommandType::Error(err.to_string());
+                return;
             }
+        };
+
+        let diffs = match self.backend.tree() {
+            Some(tree) => tree.sync(&dir),
+            None => Err(String::from("There is no tree to sync with --real")),
+        };
+
+        match diffs {
+            Ok(diffs) => {
+                self.sync = diffs;
+                self.sync_curr = 0;
+                self.command = CommandType::Sync;
+            },
+            Err(err) => self.command = CommandType::Error(err)
         }
+    }
 
-        if !found_flag {
-            for (i, e) in self.tree.root.iter().enumerate() {
-                if e.path == entry_path {
-                    self.tree.root.remove(i);
-                    break
+    fn handle_sync_input(&mut self, c: &i32, entries: &mut Vec<tree::Entry>) {
+        match self.keymap.feed(*c) {
+            Some(keys::Action::Up) => self.sync_curr = self.sync_curr.saturating_sub(1),
+            Some(keys::Action::Down) => self.sync_curr = min(self.sync_curr + 1, self.sync.len().saturating_sub(1)),
+            Some(keys::Action::Quit) => self.command = CommandType::None,
+            Some(keys::Action::Open | keys::Action::Sync) if self.read_only => self.command = CommandType::Error(String::from("Read-only mode")),
+            Some(keys::Action::Open) if !self.sync.is_empty() => {
+                let diff = self.sync.remove(self.sync_curr);
+                if let Some(tree) = self.backend.tree() {
+                    tree.apply_diff(diff);
                 }
+                self.sync_curr = min(self.sync_curr, self.sync.len().saturating_sub(1));
+                self.record_sync(entries);
+            },
+            Some(keys::Action::Sync) => {
+                if let Some(tree) = self.backend.tree() {
+                    for diff in std::mem::take(&mut self.sync) {
+                        tree.apply_diff(diff);
+                    }
+                }
+                self.command = CommandType::None;
+                self.record_sync(entries);
+            },
+            _ => {}
+        }
+    }
+
+    fn record_sync(&mut self, entries: &mut Vec<tree::Entry>) {
+        self.record_change();
+        self.update_json();
+        self.reload_entries(entries);
+    }
+
+    fn handle_search_input(&mut self, c: &i32, entries: &mut Vec<tree::Entry>, file_curr: &mut usize) {
+        match c {
+            127 if !self.input_value.is_empty() => { // BACKSPACE
+                self.input_value.pop();
+                self.input_cursor -= 1;
+            },
+            32..=126 => {
+                self.input_value.push(*c as u8 as char);
+                self.input_cursor += 1;
+            },
+            10 => { // ENTER
+                // Bring back the full listing with the cursor on the match that was selected
+                let selected = entries.get(*file_curr).map(|e| e.path.clone());
+
+                self.set_entries(entries);
+                *file_curr = entries.iter().position(|e| Some(&e.path) == selected.as_ref()).unwrap_or(0);
+                self.command = CommandType::None;
+                self.input_value = String::from("");
+                self.input_cursor = 0;
+                return;
+            },
+            _ => return
+        }
+
+        self.search = self.input_value.clone();
+        self.set_entries(entries);
+        entries.retain(|e| fuzzy::fuzzy_match(&self.search, &e.name).is_some());
+        *file_curr = 0;
+    }
+
+    fn next_match(&self, entries: &[tree::Entry], file_curr: &mut usize, forward: bool) {
+        if self.search.is_empty() || entries.is_empty() {
+            return;
+        }
+
+        for i in 1..=entries.len() {
+            let idx = if forward { (*file_curr + i) % entries.len() } else { (*file_curr + entries.len() - i) % entries.len() };
+
+            if fuzzy::fuzzy_match(&self.search, &entries[idx].name).is_some() {
+                *file_curr = idx;
+                break;
             }
         }
+    }
 
-        if delete_children {
-            let mut i = 0;
+    fn handle_find_input(&mut self, c: &i32, entries: &mut Vec<tree::Entry>, file_curr: &mut usize, top_offset: &mut i32) {
+        match self.keymap.feed(*c) {
+            Some(keys::Action::Up) => self.find_curr = self.find_curr.saturating_sub(1),
+            Some(keys::Action::Down) => self.find_curr = min(self.find_curr + 1, self.find.len().saturating_sub(1)),
+            Some(keys::Action::Quit) => self.command = CommandType::None,
+            Some(keys::Action::Open) if !self.find.is_empty() => {
+                let found = self.find[self.find_curr].clone();
 
-            while i < self.tree.root.len() {
-                if self.tree.root[i].path.starts_with(&format!("{}/", entry_path)) {
-                    self.tree.root.remove(i);
-                } else {
-                    i += 1;
-                }
+                self.command = CommandType::None;
+                let parent = match Path::new(&found.path).parent() {
+                    Some(parent) => parent.display().to_string(),
+                    None => String::from("/"),
+                };
+                go_to(self, &parent, entries, file_curr, top_offset);
+                *file_curr = entries.iter().position(|e| e.path == found.path).unwrap_or(0);
+            },
+            _ => {}
+        }
+    }
+
+    fn preview_lines(&self, entry: &tree::Entry, max_lines: usize) -> Vec<String> {
+        let mut lines: Vec<String> = vec![];
+
+        if let Some(link_path) = self.backend.link_target(&entry.path) {
+            lines.push(format!("-> {}", link_path));
+        }
+
+        let (real_path, _) = match self.resolve_path(&entry.path) {
+            Ok(resolved) => resolved,
+            Err(err) => {
+                lines.push(err.to_string());
+                return lines;
+            }
+        };
+
+        if entry.r#type == "d" || entry.r#type == "dl" || archive::is_archive(&entry.name) {
+            let children = self.backend.list(&entry.path).unwrap_or_default();
+            lines.extend(children.iter().map(|e| format!("{} {}", e.r#type, e.name)));
+        } else {
+            match preview::file_preview(Path::new(&real_path), max_lines) {
+                Ok(file_lines) => lines.extend(file_lines),
+                Err(err) => lines.push(err.to_string()),
             }
         }
 
-        if update_json {
-            self.update_json();
+        lines.truncate(max_lines);
+        lines
+    }
+
+    fn draw_preview(&self, entry: &tree::Entry, left: i32, width: i32, height: i32) {
+        for row in 1..height - 1 {
+            mv(row, left - 1);
+            addstr("|");
+        }
+
+        for (i, line) in self.preview_lines(entry, max(0, height - 2) as usize).iter().enumerate() {
+            mv(i as i32 + 1, left + 1);
+            addstr(&line.chars().take(max(0, width - 2) as usize).collect::<String>());
         }
     }
 
-    fn update_json(&self) {
-            //to_writer_pretty(&File::create("tree.json").unwrap(), &self.tree).unwrap();
-            to_writer(&File::create("tree.json").unwrap(), &self.tree).unwrap();
+    // Whether `dir` is an archive or a directory inside one
+    fn in_archive(&self, dir: &str) -> bool {
+        archive::split(dir, |p| self.backend.resolve(p).ok()).is_some()
+    }
+
+    fn open_file(&mut self, entry: &tree::Entry) {
+        if self.in_archive(&self.curr_path) {
+            self.command = CommandType::Error(String::from("Files inside archives can't be opened"));
+            return;
+        }
+
+        let path = match self.resolve_path(&entry.path) {
+            Ok((path, _)) => path,
+            Err(err) => {
+                self.command = CommandType::Error(err.to_string());
+                return;
+            }
+        };
+
+        // Hand the terminal over to the program and take it back once it exits
+        endwin();
+        let status = Command::new("sh")
+            .arg("-c")
+            .arg(self.config.open_command(Path::new(&path)))
+            .arg("sh")
+            .arg(&path)
+            .status();
+        refresh();
+
+        match status {
+            Ok(status) if !status.success() => self.command = CommandType::Error(format!("Command exited with {}", status)),
+            Ok(_) => {},
+            Err(err) => self.command = CommandType::Error(err.to_string())
+        }
+    }
+
+    fn update_json(&mut self) {
+            if self.read_only {
+                return;
+            }
+
+            if let Err(err) = self.backend.save() {
+                self.command = CommandType::Error(err);
+            }
     }
 
 }
@@ -421,39 +1239,224 @@ fn list_up(file_curr: &mut usize, top_offset: &mut i32) {
     }
 }
 
-fn list_down(file_curr: &mut usize, top_offset: &mut i32, max_y: &i32, entries: &Vec<tree::Entry>) {
-    if entries.len() > 0 {
+fn list_down(file_curr: &mut usize, top_offset: &mut i32, max_y: &i32, entries: &[tree::Entry]) {
+    if !entries.is_empty() {
         *file_curr = min(*file_curr + 1, entries.len() - 1);
 
         let x: i32 = max_y - 3 + *top_offset; 
-        if (*file_curr as i32) > x.try_into().unwrap() {
+        if (*file_curr as i32) > x {
             *top_offset += 1; 
         }
     }
 }
 
-fn move_back(ui: &mut Ui, entries: &mut Vec<tree::Entry>, top_offset: &mut i32, file_curr: &mut usize) {
-        ui.set_entries(&mut *entries); 
-        *top_offset = 0;
-        *file_curr = 0;
+fn scroll_into_view(file_curr: &usize, top_offset: &mut i32, max_y: &i32) {
+    let curr = *file_curr as i32;
+
+    if curr < *top_offset {
+        *top_offset = curr;
+    } else if curr > max_y - 3 + *top_offset {
+        *top_offset = curr - (max_y - 3);
+    }
+}
+
+fn selected_entries(entries: &[tree::Entry], file_curr: usize, select_start: &Option<i32>) -> Vec<tree::Entry> {
+    match *select_start {
+        None => entries.get(file_curr).into_iter().cloned().collect(),
+        Some(start) => {
+            let to = min(max(start as usize, file_curr), entries.len() - 1);
+            let from = min(min(start as usize, file_curr), to);
+            entries[from..=to].to_vec()
+        }
+    }
+}
+
+fn init_colors(theme: &config::Theme) -> Result<(), String> {
+    let pairs = [
+        (HIGHLIGHT_PAIR, &theme.selection, COLOR_BLACK, COLOR_WHITE),
+        (ERROR_PAIR, &theme.error, COLOR_RED, COLOR_BLACK),
+        (FILE_PAIR, &theme.file, COLOR_WHITE, COLOR_BLACK),
+        (DIR_PAIR, &theme.dir, COLOR_BLUE, COLOR_BLACK),
+        (LINK_PAIR, &theme.link, COLOR_CYAN, COLOR_BLACK),
+    ];
+
+    for (pair, colors, fg, bg) in pairs {
+        let (fg, bg) = match colors {
+            Some(colors) => (
+                colors.fg.as_deref().map(config::parse_color).unwrap_or(Ok(fg))?,
+                colors.bg.as_deref().map(config::parse_color).unwrap_or(Ok(bg))?,
+            ),
+            None => (fg, bg),
+        };
+
+        init_pair(pair, fg, bg);
+    }
+
+    Ok(())
+}
+
+fn enter_dir(ui: &mut Ui, entries: &mut Vec<tree::Entry>, file_curr: &mut usize, top_offset: &mut i32) {
+    let path = format!("{}{}{}", ui.curr_path, 
+                           { if  ui.curr_path == "/" { "" } else { "/" } }, entries[*file_curr].name); 
+    go_to(ui, &path, entries, file_curr, top_offset);
+}
+
+// The navigation state that is shown right now
+fn current_tab(ui: &Ui, file_curr: usize, top_offset: i32, select_start: Option<i32>) -> tabs::Tab {
+    tabs::Tab {
+        curr_path: ui.curr_path.clone(),
+        file_curr,
+        top_offset,
+        select_start,
+    }
+}
+
+fn go_to(ui: &mut Ui, path: &str, entries: &mut Vec<tree::Entry>, file_curr: &mut usize, top_offset: &mut i32) {
+    ui.history.visit(path);
+    open_dir(ui, path, entries, file_curr, top_offset);
+}
+
+// Shows `path` with the cursor where it was left, without adding it to the history
+fn open_dir(ui: &mut Ui, path: &str, entries: &mut Vec<tree::Entry>, file_curr: &mut usize, top_offset: &mut i32) {
+    ui.history.save_position(&ui.curr_path, *file_curr, *top_offset);
+
+    ui.curr_path = path.to_string();
+    ui.set_entries(entries);
+    ui.set_parent_path();
+    (*file_curr, *top_offset) = ui.history.position(path);
+    ui.frecency.visit(path);
+}
+
+// Switching to a tab or pane is a visit too, the cursor goes where the tab left it
+fn load_tab(ui: &mut Ui, tab: tabs::Tab, entries: &mut Vec<tree::Entry>, file_curr: &mut usize, top_offset: &mut i32, select_start: &mut Option<i32>) {
+    go_to(ui, &tab.curr_path, entries, file_curr, top_offset);
+    *file_curr = tab.file_curr;
+    *top_offset = tab.top_offset;
+    *select_start = tab.select_start;
+}
+
+fn switch_tab(ui: &mut Ui, to: usize, entries: &mut Vec<tree::Entry>, file_curr: &mut usize, top_offset: &mut i32, select_start: &mut Option<i32>) {
+    if to >= ui.tabs.len() {
+        return;
+    }
+
+    ui.tabs[ui.tab_curr] = current_tab(ui, *file_curr, *top_offset, *select_start);
+    ui.tab_curr = to;
+    load_tab(ui, ui.tabs[to].clone(), entries, file_curr, top_offset, select_start);
 }
 
 fn main() {
+    let cwd = std::env::current_dir().map(|dir| dir.display().to_string()).unwrap_or_else(|_| String::from("/"));
+    let args = match args::parse_args(std::env::args().skip(1), &cwd) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("error: {}\n\n{}", err, args::usage());
+            std::process::exit(2);
+        }
+    };
+
+    if args.help {
+        println!("{}", args::usage());
+        return;
+    } else if args.version {
+        println!("{}", args::version());
+        return;
+    }
+
+    let (config, mut startup_error) = match config::parse_config() {
+        Ok(config) => (config, None),
+        Err(err) => (config::Config::default(), Some(err)),
+    };
+
+    let tree_path = PathBuf::from(args.tree.as_deref().or(config.tree.as_deref()).unwrap_or("tree.json"));
+    // --real only looks at the disk, the tree isn't loaded or written
+    let backend: Box<dyn backend::Backend> = if args.real {
+        Box::new(disk_backend::DiskBackend::default())
+    } else {
+        match tree_backend::TreeBackend::new(tree_path.clone()) {
+            Ok(backend) => Box::new(backend),
+            Err(err) => {
+                eprintln!("error: {}", err);
+                std::process::exit(1);
+            }
+        }
+    };
+
+    let bookmarks_path = json_file::next_to_tree(&tree_path, "bookmarks.json");
+    let bookmarks = match bookmarks::Bookmarks::load(bookmarks_path.clone()) {
+        Ok(bookmarks) => bookmarks,
+        Err(err) => {
+            startup_error = Some(err);
+            bookmarks::Bookmarks::empty(bookmarks_path)
+        }
+    };
+
+    let frecency_path = json_file::next_to_tree(&tree_path, "frecency.json");
+    let frecency = match frecency::Frecency::load(frecency_path.clone()) {
+        Ok(frecency) => frecency,
+        Err(err) => {
+            startup_error = Some(err);
+            frecency::Frecency::empty(frecency_path)
+        }
+    };
+
+    let keymap = match keys::KeyMap::new(&config.keys) {
+        Ok(keymap) => keymap,
+        Err(err) => {
+            startup_error = Some(err);
+            keys::KeyMap::new(&HashMap::new()).unwrap()
+        }
+    };
+
     initscr();
     noecho();
     curs_set(CURSOR_VISIBILITY::CURSOR_INVISIBLE);
 
     start_color();
-    init_pair(REGULAR_PAIR, COLOR_WHITE, COLOR_BLACK);
-    init_pair(HIGHLIGHT_PAIR, COLOR_BLACK, COLOR_WHITE);
+    use_default_colors();
+    if let Err(err) = init_colors(&config.colors) {
+        init_colors(&config::Theme::default()).unwrap();
+        startup_error = Some(err);
+    }
+
+    let start_path = args.start_path.clone().or_else(|| config.start_path.clone()).unwrap_or_else(|| String::from("/"));
 
     let mut ui = Ui { 
-        curr_path: String::from("/"),
+        tabs: vec![tabs::Tab::new(&start_path)],
+        tab_curr: 0,
+        other_pane: None,
+        curr_path: start_path.clone(),
         parent_path: String::from("/"),
         command: CommandType::None,
         input_cursor: 0,
         input_value: String::from(""),
-        tree: tree::parse_tree()
+        completions: vec![],
+        backend,
+        clipboard: vec![],
+        clipboard_cut: false,
+        journal: journal::Journal::new(json_file::next_to_tree(&tree_path, ".journal")),
+        trash: vec![],
+        trash_curr: 0,
+        sync: vec![],
+        sync_curr: 0,
+        search: String::new(),
+        find_mode: find::FindMode::Name,
+        archive_format: archive::ArchiveFormat::TarGz,
+        find: vec![],
+        find_curr: 0,
+        bookmarks,
+        bookmarks_curr: 0,
+        history: history::History::new(&start_path),
+        frecency,
+        recent: vec![],
+        recent_curr: 0,
+        sort_orders: HashMap::new(),
+        columns: columns::Columns::new(),
+        metadata: columns::MetadataCache::new(),
+        preview: false,
+        config,
+        keymap,
+        read_only: args.read_only,
     };
 
     let mut quit = false;
@@ -462,97 +1465,320 @@ fn main() {
     let mut top_offset: i32 = 0;
     let mut entries: Vec<tree::Entry> = Vec::new(); 
 
-    ui.set_entries(&mut entries);
-    ui.set_parent_path();
+    let session_path = json_file::next_to_tree(&tree_path, "session.json");
+    let restored = if args.no_restore {
+        None
+    } else {
+        match session::Session::load(&session_path) {
+            Ok(Some(session)) => ui.restore(session, args.start_path.is_none()),
+            Ok(None) => None,
+            Err(err) => {
+                ui.command = CommandType::Error(err);
+                None
+            }
+        }
+    };
+
+    match restored {
+        Some(tab) => load_tab(&mut ui, tab, &mut entries, &mut file_curr, &mut top_offset, &mut select_start),
+        None => {
+            ui.set_entries(&mut entries);
+            ui.set_parent_path();
+        }
+    }
+
+    if let Some(err) = startup_error {
+        ui.command = CommandType::Error(err);
+    }
 
     let mut max_x: i32 = 0;
     let mut max_y: i32 = 0;
 
     while !quit {
+        file_curr = min(file_curr, entries.len().saturating_sub(1));
+
         erase();
         getmaxyx(stdscr(), &mut max_y, &mut max_x);
+        scroll_into_view(&file_curr, &mut top_offset, &max_y);
 
         ui.begin(&max_x, &max_y);
-        for (i, entry) in entries.iter().enumerate() {
-            if i >= top_offset.try_into().unwrap() && (i as i32) - top_offset < max_y - 2 {
-                let mut pair = { 
-                    if file_curr == i {
-                        HIGHLIGHT_PAIR
-                    } else {
-                        REGULAR_PAIR
-                    }
-                };
 
-                if !select_start.is_none() &&  
-                     ((select_start.unwrap() <= i as i32 &&  file_curr as i32 >= i as i32) ||
-                        (select_start.unwrap() >= i as i32 &&  file_curr as i32 <= i as i32)) {
-                        pair = HIGHLIGHT_PAIR;
-                }
+        // The listing takes the left half of the screen while the preview or the other pane is open
+        let list_width = if ui.preview || ui.other_pane.is_some() { max_x / 2 } else { max_x };
+
+        if let CommandType::Trash = ui.command {
+            let labels: Vec<String> = ui.trash.iter().map(|item| format!("{}  {}", item.deletion_date, item.path)).collect();
+            ui.draw_popup(&labels, ui.trash_curr, &max_y);
+        } else if let CommandType::FindResults = ui.command {
+            let labels: Vec<String> = ui.find.iter().map(|e| format!("{} {}", e.r#type, e.path)).collect();
+            ui.draw_popup(&labels, ui.find_curr, &max_y);
+        } else if let CommandType::Bookmarks = ui.command {
+            let labels: Vec<String> = ui.bookmarks.list().iter().map(|b| format!("{}  {}", b.name, b.path)).collect();
+            ui.draw_popup(&labels, ui.bookmarks_curr, &max_y);
+        } else if let CommandType::Recent = ui.command {
+            ui.draw_popup(&ui.recent.clone(), ui.recent_curr, &max_y);
+        } else if let CommandType::Sync = ui.command {
+            let labels: Vec<String> = ui.sync.iter().map(|diff| diff.label()).collect();
+            ui.draw_popup(&labels, ui.sync_curr, &max_y);
+        } else {
+            let active = current_tab(&ui, file_curr, top_offset, select_start);
+            ui.draw_entries(&entries, &active, 0, list_width, max_y);
 
-                let label = format!("{} {}", entry.r#type, &entry.name);
-                ui.list_item(&label, pair, &((i as i32) - top_offset));
+            if let Some(pane) = ui.other_pane.clone() {
+                ui.draw_pane(&pane, list_width + 1, max_x - list_width - 1, max_y);
             }
         }
 
+        if ui.preview && ui.other_pane.is_none() && !matches!(ui.command, CommandType::Trash | CommandType::Sync | CommandType::FindResults | CommandType::Bookmarks | CommandType::Recent) && !entries.is_empty() {
+            ui.draw_preview(&entries[file_curr], list_width + 1, max_x - list_width - 1, max_y);
+        }
+
         mv(max_y - 1, 0);
         refresh();
 
         let c = getch();
         if c == 27 { // ESC
+            if let CommandType::Search = ui.command {
+                ui.search = String::new();
+                ui.set_entries(&mut entries);
+            }
+
             ui.command = CommandType::None;
+            ui.input_value = String::from("");
+            ui.input_cursor = 0;
+            ui.completions.clear();
+            ui.keymap.reset();
             select_start = None;
+            continue;
         }
 
         match ui.command {
             CommandType::None => {
-                match c as u8 as char {
-                    'q' => quit = true,
-                    'd' => if entries.len() != 0 { ui.command = CommandType::Delete },
-                    'o' => ui.command = CommandType::NewFile,
-                    'O' => ui.command = CommandType::NewDir,
-                    'p' => ui.command = CommandType::NewLink,
-                    'k' => list_up(&mut file_curr, &mut top_offset),
-                    'j' => list_down(&mut file_curr, &mut top_offset, &max_y, &entries),
-                    'v' => select_start = Some(file_curr as i32),
-                    'r' => ui.set_entries(&mut entries),
-                    'R' => { 
-                            ui.tree = tree::parse_tree();
-                            ui.set_entries(&mut entries);
-                    },
-                    'h' => {
-                            ui.curr_path = ui.parent_path.to_string();
-                            move_back(&mut ui, &mut entries, &mut top_offset, &mut file_curr);
-                            ui.set_parent_path();
+                let is_dir = !entries.is_empty() && (entries[file_curr].r#type == "d" || entries[file_curr].r#type == "dl");
+                let is_file = !entries.is_empty() && (entries[file_curr].r#type == "f" || entries[file_curr].r#type == "fl");
+                let is_archive = is_file && archive::is_archive(&entries[file_curr].name);
+                let in_archive = ui.in_archive(&ui.curr_path);
+
+                match ui.keymap.feed(c) {
+                    Some(action) if ui.read_only && action.is_mutating() => ui.command = CommandType::Error(String::from("Read-only mode")),
+                    Some(action) if in_archive && action.is_mutating() && !matches!(action, keys::Action::Undo | keys::Action::Redo) => {
+                            ui.command = CommandType::Error(String::from("Archives can't be changed"));
+                    },
+                    Some(keys::Action::Quit) => quit = true,
+                    Some(keys::Action::Delete) if !entries.is_empty() => ui.command = CommandType::Delete,
+                    Some(keys::Action::DeletePermanently) if !entries.is_empty() => ui.command = CommandType::PermanentDelete,
+                    Some(keys::Action::Trash) => ui.open_trash(),
+                    Some(keys::Action::GoTo) => ui.command = CommandType::GoTo,
+                    Some(keys::Action::Jump) => ui.command = CommandType::Jump,
+                    Some(keys::Action::Mark) => ui.command = CommandType::Mark,
+                    Some(keys::Action::JumpToMark) => ui.command = CommandType::JumpToMark,
+                    Some(keys::Action::Bookmarks) => {
+                            ui.command = CommandType::Bookmarks;
+                            ui.bookmarks_curr = 0;
+                    },
+                    Some(keys::Action::Sync) => ui.open_sync(),
+                    Some(keys::Action::Search) => {
+                            ui.command = CommandType::Search;
+                            ui.search = String::new();
                             select_start = None;
                     },
-                    '\n' => if entries.len() != 0 && (entries[file_curr].r#type == "d" || entries[file_curr].r#type == "dl")  {
-                            ui.curr_path = format!("{}{}{}", ui.curr_path, 
-                                                   { if  ui.curr_path == "/" { "" } else { "/" } }, entries[file_curr].name.to_string()); 
-                            ui.set_entries(&mut entries);
-                            top_offset = 0;
-                            file_curr = 0;
-                            select_start = None; 
-
-                            ui.set_parent_path();
-                    }
-                    'l' => if entries.len() != 0 && (entries[file_curr].r#type == "d" || entries[file_curr].r#type == "dl")  {
-                            ui.curr_path = format!("{}{}{}", ui.curr_path, 
-                                                   { if  ui.curr_path == "/" { "" } else { "/" } }, entries[file_curr].name.to_string()); 
-                            ui.set_entries(&mut entries);
-                            top_offset = 0;
-                            file_curr = 0;
+                    Some(keys::Action::Find) => ui.command = CommandType::Find,
+                    Some(keys::Action::Columns) => ui.command = CommandType::Columns,
+                    Some(keys::Action::Preview) => ui.preview = !ui.preview,
+                    Some(keys::Action::Sort) => ui.set_sort_order(ui.sort_order().next(), &mut entries),
+                    Some(keys::Action::ReverseSort) => {
+                            let mut order = ui.sort_order();
+                            order.reverse = !order.reverse;
+                            ui.set_sort_order(order, &mut entries);
+                    },
+                    Some(keys::Action::NextMatch) => ui.next_match(&entries, &mut file_curr, true),
+                    Some(keys::Action::PrevMatch) => ui.next_match(&entries, &mut file_curr, false),
+                    Some(keys::Action::NewFile) => ui.command = CommandType::NewFile,
+                    Some(keys::Action::NewDir) => ui.command = CommandType::NewDir,
+                    Some(keys::Action::NewLink) => {
+                            ui.command = CommandType::NewLink;
+                            // With two panes the link points at the entry under the cursor
+                            if ui.other_pane.is_some() && !entries.is_empty() {
+                                ui.input_value = entries[file_curr].path.clone();
+                                ui.input_cursor = ui.input_value.chars().count() as i32;
+                            }
+                    },
+                    Some(keys::Action::TogglePanes) => {
+                            ui.other_pane = match ui.other_pane {
+                                Some(_) => None,
+                                None => Some(tabs::Tab::new(&ui.curr_path)),
+                            };
+                    },
+                    Some(keys::Action::SwitchPane) if ui.other_pane.is_some() => {
+                            let pane = ui.other_pane.replace(current_tab(&ui, file_curr, top_offset, select_start)).unwrap();
+                            load_tab(&mut ui, pane, &mut entries, &mut file_curr, &mut top_offset, &mut select_start);
+                    },
+                    Some(keys::Action::Compress) if !entries.is_empty() => {
+                            ui.command = CommandType::Compress;
+                            ui.input_value = Path::new(&entries[file_curr].name).file_stem().unwrap_or_default().to_string_lossy().to_string();
+                            ui.input_cursor = ui.input_value.chars().count() as i32;
+                    },
+                    Some(keys::Action::Rename) if !entries.is_empty() => {
+                            ui.command = CommandType::Rename;
+                            ui.input_value = entries[file_curr].name.to_string();
+                            ui.input_cursor = ui.input_value.chars().count() as i32;
+                    },
+                    Some(keys::Action::Up) => list_up(&mut file_curr, &mut top_offset),
+                    Some(keys::Action::Down) => list_down(&mut file_curr, &mut top_offset, &max_y, &entries),
+                    Some(keys::Action::Select) => select_start = Some(file_curr as i32),
+                    Some(keys::Action::Yank) if !entries.is_empty() => {
+                            ui.set_clipboard(selected_entries(&entries, file_curr, &select_start), false);
                             select_start = None;
-
-                            ui.set_parent_path();
+                    },
+                    Some(keys::Action::Cut) if !entries.is_empty() => {
+                            ui.set_clipboard(selected_entries(&entries, file_curr, &select_start), true);
+                            select_start = None;
+                    },
+                    Some(keys::Action::Paste) => ui.paste(&mut entries),
+                    Some(action @ (keys::Action::CopyToTab | keys::Action::MoveToTab)) if ui.other_pane.is_some() && !entries.is_empty() => {
+                            let dir = ui.link_dir();
+                            let selected = selected_entries(&entries, file_curr, &select_start);
+                            ui.copy_to_dir(&dir, &selected, action == keys::Action::MoveToTab, &mut entries);
+                            select_start = None;
+                    },
+                    Some(keys::Action::CopyToTab) | Some(keys::Action::MoveToTab) if ui.tabs.len() < 2 => {
+                            ui.command = CommandType::Error(String::from("There is only one tab"));
+                    },
+                    Some(keys::Action::CopyToTab) if !entries.is_empty() => ui.command = CommandType::CopyToTab,
+                    Some(keys::Action::MoveToTab) if !entries.is_empty() => ui.command = CommandType::MoveToTab,
+                    Some(keys::Action::NewTab) => {
+                            let to = ui.tab_curr + 1;
+                            ui.tabs.insert(to, tabs::Tab::new(&ui.curr_path));
+                            switch_tab(&mut ui, to, &mut entries, &mut file_curr, &mut top_offset, &mut select_start);
+                    },
+                    Some(keys::Action::CloseTab) if ui.tabs.len() > 1 => {
+                            ui.tabs.remove(ui.tab_curr);
+                            ui.tab_curr = min(ui.tab_curr, ui.tabs.len() - 1);
+                            let tab = ui.tabs[ui.tab_curr].clone();
+                            load_tab(&mut ui, tab, &mut entries, &mut file_curr, &mut top_offset, &mut select_start);
+                    },
+                    Some(keys::Action::NextTab) => {
+                            let to = (ui.tab_curr + 1) % ui.tabs.len();
+                            switch_tab(&mut ui, to, &mut entries, &mut file_curr, &mut top_offset, &mut select_start);
+                    },
+                    Some(keys::Action::PrevTab) => {
+                            let to = (ui.tab_curr + ui.tabs.len() - 1) % ui.tabs.len();
+                            switch_tab(&mut ui, to, &mut entries, &mut file_curr, &mut top_offset, &mut select_start);
+                    },
+                    Some(keys::Action::GoToTab(to)) => switch_tab(&mut ui, to, &mut entries, &mut file_curr, &mut top_offset, &mut select_start),
+                    Some(keys::Action::Undo) => ui.undo(&mut entries),
+                    Some(keys::Action::Redo) => ui.redo(&mut entries),
+                    Some(keys::Action::Reload) => ui.reload_entries(&mut entries),
+                    Some(keys::Action::HardReload) => { 
+                            if let Some(Err(err)) = ui.backend.tree().map(|tree| tree.reload()) {
+                                ui.command = CommandType::Error(err);
+                            }
+                            ui.reload_entries(&mut entries);
+                    },
+                    Some(keys::Action::Back) => {
+                            let parent = ui.parent_path.clone();
+                            go_to(&mut ui, &parent, &mut entries, &mut file_curr, &mut top_offset);
+                            select_start = None;
+                    },
+                    Some(keys::Action::HistoryBack) => match ui.history.back() {
+                            Some(path) => {
+                                open_dir(&mut ui, &path, &mut entries, &mut file_curr, &mut top_offset);
+                                select_start = None;
+                            },
+                            None => ui.command = CommandType::Error(String::from("Already at the oldest folder")),
+                    },
+                    Some(keys::Action::HistoryForward) => match ui.history.forward() {
+                            Some(path) => {
+                                open_dir(&mut ui, &path, &mut entries, &mut file_curr, &mut top_offset);
+                                select_start = None;
+                            },
+                            None => ui.command = CommandType::Error(String::from("Already at the newest folder")),
+                    },
+                    Some(keys::Action::Recent) => {
+                            ui.recent = ui.history.recent();
+                            ui.recent_curr = 0;
+                            ui.command = CommandType::Recent;
+                    },
+                    Some(keys::Action::EnterDir) | Some(keys::Action::Open) if is_dir || is_archive => {
+                            enter_dir(&mut ui, &mut entries, &mut file_curr, &mut top_offset);
+                            select_start = None;
+                    },
+                    Some(keys::Action::Open) if is_file => {
+                            ui.open_file(&entries[file_curr].clone());
+                            ui.metadata.invalidate(&ui.curr_path);
                     },
                     _ => {}
-
                 }
             },
+            CommandType::Trash => ui.handle_trash_input(&c, &mut entries),
+            CommandType::Columns => ui.columns.toggle(c as u8 as char),
+            CommandType::Mark => ui.set_mark(c),
+            CommandType::GoTo if c == 10 => { // ENTER
+                    if let Some(path) = ui.go_to_target() {
+                        go_to(&mut ui, &path, &mut entries, &mut file_curr, &mut top_offset);
+                        select_start = None;
+                    }
+            },
+            CommandType::Jump if c == 10 => { // ENTER
+                    if let Some(path) = ui.jump_target() {
+                        go_to(&mut ui, &path, &mut entries, &mut file_curr, &mut top_offset);
+                        select_start = None;
+                    }
+            },
+            CommandType::JumpToMark => {
+                    ui.command = CommandType::None;
+
+                    match ui.bookmarks.mark(c as u8 as char).cloned() {
+                        Some(path) => {
+                            go_to(&mut ui, &path, &mut entries, &mut file_curr, &mut top_offset);
+                            select_start = None;
+                        },
+                        None => ui.command = CommandType::Error(format!("Mark {} is not set", c as u8 as char)),
+                    }
+            },
+            CommandType::Recent => {
+                    if let Some(path) = ui.handle_recent_input(&c) {
+                        go_to(&mut ui, &path, &mut entries, &mut file_curr, &mut top_offset);
+                        select_start = None;
+                    }
+            },
+            CommandType::Bookmarks => {
+                    if let Some(path) = ui.handle_bookmarks_input(&c) {
+                        go_to(&mut ui, &path, &mut entries, &mut file_curr, &mut top_offset);
+                        select_start = None;
+                    }
+            },
+            CommandType::CopyToTab | CommandType::MoveToTab => {
+                    let cut = matches!(ui.command, CommandType::MoveToTab);
+                    ui.command = CommandType::None;
+
+                    if let Some(n) = (c as u8 as char).to_digit(10).filter(|n| *n > 0) {
+                        let selected = selected_entries(&entries, file_curr, &select_start);
+                        ui.copy_to_tab(n as usize - 1, &selected, cut, &mut entries);
+                        select_start = None;
+                    }
+            },
+            CommandType::Sync => ui.handle_sync_input(&c, &mut entries),
+            CommandType::Search => ui.handle_search_input(&c, &mut entries, &mut file_curr),
+            CommandType::FindResults => {
+                    ui.handle_find_input(&c, &mut entries, &mut file_curr, &mut top_offset);
+                    select_start = None;
+            },
             _ => ui.handle_input(&c, &mut entries, &file_curr, &select_start) 
         }
     }
 
+    ui.journal.clear();
+    let session = ui.session(current_tab(&ui, file_curr, top_offset, select_start));
     endwin();
-}
 
+    if !ui.read_only {
+        if let Err(err) = session.save(&session_path) {
+            eprintln!("error: {}", err);
+        }
+
+        if let Err(err) = ui.frecency.save() {
+            eprintln!("error: {}", err);
+        }
+    }
+}
@@ -0,0 +1,41 @@
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::Path;
+
+// Only the start of a file is read, the pane can't show more than that anyway
+const PREVIEW_BYTES: u64 = 64 * 1024;
+
+pub fn file_preview(path: &Path, max_lines: usize) -> io::Result<Vec<String>> {
+    // Opening a fifo or a device can block until something writes to it
+    if !fs::metadata(path)?.file_type().is_file() {
+        return Ok(vec![String::from("Not a regular file")]);
+    }
+
+    let mut data: Vec<u8> = Vec::new();
+    File::open(path)?.take(PREVIEW_BYTES).read_to_end(&mut data)?;
+
+    // A cut off multi-byte char at the end doesn't make a text file binary
+    let valid_utf8 = match std::str::from_utf8(&data) {
+        Ok(_) => true,
+        Err(err) => err.error_len().is_none(),
+    };
+
+    if data.contains(&0) || !valid_utf8 {
+        return Ok(hex_dump(&data, max_lines));
+    }
+
+    Ok(String::from_utf8_lossy(&data)
+        .lines()
+        .take(max_lines)
+        .map(|line| line.replace('\t', "    ").chars().map(|c| if c.is_control() { '?' } else { c }).collect())
+        .collect())
+}
+
+fn hex_dump(data: &[u8], max_lines: usize) -> Vec<String> {
+    data.chunks(16).take(max_lines).enumerate().map(|(i, chunk)| {
+        let hex: Vec<String> = chunk.iter().map(|b| format!("{:02x}", b)).collect();
+        let ascii: String = chunk.iter().map(|b| if b.is_ascii_graphic() || *b == b' ' { *b as char } else { '.' }).collect();
+
+        format!("{:08x}  {:47}  |{}|", i * 16, hex.join(" "), ascii)
+    }).collect()
+}
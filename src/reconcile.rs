@@ -0,0 +1,95 @@
+use std::collections::HashSet;
+use std::io;
+use std::path::Path;
+
+use crate::get_entries::get_entries;
+use crate::tree;
+
+pub enum Diff {
+    // Exists on disk but is missing from tree.json
+    DiskOnly(tree::Entry),
+    // Listed in tree.json but missing on disk
+    TreeOnly(tree::Entry),
+}
+
+impl Diff {
+    pub fn label(&self) -> String {
+        match self {
+            Diff::DiskOnly(e) => format!("+ {} {} (not in tree, enter to import)", e.r#type, e.path),
+            Diff::TreeOnly(e) => format!("- {} {} (not on disk, enter to prune)", e.r#type, e.path),
+        }
+    }
+}
+
+// Compares everything below `dir` in the tree with the disk. Directories that are new
+// on disk are walked as well, their contents are listed right after them.
+pub fn reconcile(tree: &tree::Tree, dir: &str) -> io::Result<Vec<Diff>> {
+    let mut diffs: Vec<Diff> = Vec::new();
+    let prefix = if dir == "/" { String::from("/") } else { format!("{}/", dir) };
+
+    let mut known: Vec<&tree::Entry> = tree.root.iter().filter(|e| e.path.starts_with(&prefix)).collect();
+    known.sort_by(|a, b| a.path.cmp(&b.path));
+    let known_paths: HashSet<&str> = known.iter().map(|e| e.path.as_str()).collect();
+
+    let mut missing: HashSet<&str> = HashSet::new();
+    let under_missing = |path: &str, missing: &HashSet<&str>| {
+        Path::new(path).ancestors().skip(1).any(|a| a.to_str().map(|a| missing.contains(a)).unwrap_or(false))
+    };
+
+    for e in known.iter() {
+        if under_missing(&e.path, &missing) {
+            continue;
+        }
+
+        if Path::new(&e.path).symlink_metadata().is_err() {
+            missing.insert(&e.path);
+            diffs.push(Diff::TreeOnly((*e).clone()));
+        }
+    }
+
+    fn is_real_dir(e: &tree::Entry) -> bool {
+        e.r#type == "d" && !Path::new(&e.path).symlink_metadata().map(|meta| meta.file_type().is_symlink()).unwrap_or(true)
+    }
+
+    // Everything below a directory the tree doesn't know is new as well
+    fn walk_new(dir: &str, diffs: &mut Vec<Diff>) {
+        for e in get_entries(dir).unwrap_or_default() {
+            let path = e.path.clone();
+            let is_dir = is_real_dir(&e);
+
+            diffs.push(Diff::DiskOnly(e));
+            if is_dir {
+                walk_new(&path, diffs);
+            }
+        }
+    }
+
+    let mut dirs: Vec<&str> = vec![dir];
+    dirs.extend(known.iter().filter(|e| e.r#type == "d" && !missing.contains(e.path.as_str())).map(|e| e.path.as_str()));
+
+    for d in dirs {
+        if under_missing(d, &missing) {
+            continue;
+        }
+
+        let disk_entries = match get_entries(d) {
+            Ok(disk_entries) => disk_entries,
+            Err(err) if d == dir => return Err(err),
+            Err(_) => continue,
+        };
+
+        for e in disk_entries {
+            if !known_paths.contains(e.path.as_str()) {
+                let path = e.path.clone();
+                let is_dir = is_real_dir(&e);
+
+                diffs.push(Diff::DiskOnly(e));
+                if is_dir {
+                    walk_new(&path, &mut diffs);
+                }
+            }
+        }
+    }
+
+    Ok(diffs)
+}
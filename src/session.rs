@@ -0,0 +1,33 @@
+use std::collections::HashMap;
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+
+use crate::columns;
+use crate::find;
+use crate::json_file;
+use crate::sort;
+use crate::tabs;
+
+// What the explorer looked like when it was closed
+#[derive(Serialize, Deserialize)]
+pub struct Session {
+    pub tabs: Vec<tabs::Tab>,
+    pub tab_curr: usize,
+    pub other_pane: Option<tabs::Tab>,
+    pub sort_orders: HashMap<String, sort::SortOrder>,
+    pub columns: columns::Columns,
+    pub preview: bool,
+    pub search: String,
+    pub find_mode: find::FindMode,
+}
+
+impl Session {
+    // None when there is no saved session yet
+    pub fn load(path: &Path) -> Result<Option<Session>, String> {
+        json_file::load(path)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        json_file::save(path, self)
+    }
+}
@@ -0,0 +1,179 @@
+use std::cmp::Ordering;
+use std::fs::Metadata;
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+
+use crate::backend::Backend;
+use crate::tree;
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum SortKey {
+    // The order entries have in tree.json
+    Tree,
+    Name,
+    Type,
+    Size,
+    Modified,
+    Extension,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct SortOrder {
+    pub key: SortKey,
+    pub reverse: bool,
+}
+
+impl SortOrder {
+    pub fn new() -> SortOrder {
+        SortOrder { key: SortKey::Tree, reverse: false }
+    }
+
+    pub fn next(&self) -> SortOrder {
+        let key = match self.key {
+            SortKey::Tree => SortKey::Name,
+            SortKey::Name => SortKey::Type,
+            SortKey::Type => SortKey::Size,
+            SortKey::Size => SortKey::Modified,
+            SortKey::Modified => SortKey::Extension,
+            SortKey::Extension => SortKey::Tree,
+        };
+
+        SortOrder { key, reverse: self.reverse }
+    }
+
+    pub fn label(&self) -> String {
+        let key = match self.key {
+            SortKey::Tree => "tree",
+            SortKey::Name => "name",
+            SortKey::Type => "type",
+            SortKey::Size => "size",
+            SortKey::Modified => "modified",
+            SortKey::Extension => "extension",
+        };
+
+        if self.reverse { format!("{} (reversed)", key) } else { key.to_string() }
+    }
+}
+
+// Compares runs of digits by their numeric value so "file2" sorts before "file10"
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a.chars().peekable(), b.chars().peekable());
+
+    loop {
+        match (a.peek().copied(), b.peek().copied()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let mut x_num = String::new();
+                let mut y_num = String::new();
+                while let Some(c) = a.next_if(|c| c.is_ascii_digit()) {
+                    x_num.push(c);
+                }
+                while let Some(c) = b.next_if(|c| c.is_ascii_digit()) {
+                    y_num.push(c);
+                }
+
+                let (x_num, y_num) = (x_num.trim_start_matches('0'), y_num.trim_start_matches('0'));
+                let ord = x_num.len().cmp(&y_num.len()).then_with(|| x_num.cmp(y_num));
+                if ord != Ordering::Equal {
+                    return ord;
+                }
+            },
+            (Some(x), Some(y)) => {
+                let ord = x.to_lowercase().cmp(y.to_lowercase());
+                if ord != Ordering::Equal {
+                    return ord;
+                }
+                a.next();
+                b.next();
+            },
+        }
+    }
+}
+
+fn is_dir(entry: &tree::Entry) -> bool {
+    entry.r#type == "d" || entry.r#type == "dl"
+}
+
+fn extension(entry: &tree::Entry) -> String {
+    Path::new(&entry.name).extension().map(|ext| ext.to_string_lossy().to_lowercase()).unwrap_or_default()
+}
+
+pub fn sort_entries(entries: &mut Vec<tree::Entry>, order: &SortOrder, backend: &dyn Backend) {
+    if order.key == SortKey::Tree {
+        if order.reverse {
+            entries.reverse();
+        }
+        return;
+    }
+
+    // Links are stat'ed through their target
+    let mut keyed: Vec<(tree::Entry, Option<Metadata>)> = entries.drain(..).map(|e| {
+        let meta = match order.key {
+            SortKey::Size | SortKey::Modified => backend.stat(&e.path).ok(),
+            _ => None,
+        };
+        (e, meta)
+    }).collect();
+
+    keyed.sort_by(|(a, a_meta), (b, b_meta)| {
+        let ord = match order.key {
+            SortKey::Type => is_dir(b).cmp(&is_dir(a)),
+            SortKey::Size => a_meta.as_ref().map(|m| m.len()).cmp(&b_meta.as_ref().map(|m| m.len())),
+            SortKey::Modified => a_meta.as_ref().and_then(|m| m.modified().ok()).cmp(&b_meta.as_ref().and_then(|m| m.modified().ok())),
+            SortKey::Extension => extension(a).cmp(&extension(b)),
+            SortKey::Name | SortKey::Tree => Ordering::Equal,
+        };
+
+        ord.then_with(|| natural_cmp(&a.name, &b.name))
+    });
+
+    if order.reverse {
+        keyed.reverse();
+    }
+
+    entries.extend(keyed.into_iter().map(|(e, _)| e));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disk_backend::DiskBackend;
+
+    #[test]
+    fn compares_numbers_by_value() {
+        assert_eq!(natural_cmp("file2", "file10"), Ordering::Less);
+        assert_eq!(natural_cmp("file010", "file9"), Ordering::Greater);
+        assert_eq!(natural_cmp("v1.10", "v1.9"), Ordering::Greater);
+        assert_eq!(natural_cmp("a007", "a7"), Ordering::Equal);
+    }
+
+    #[test]
+    fn ignores_case() {
+        assert_eq!(natural_cmp("Apple", "banana"), Ordering::Less);
+        assert_eq!(natural_cmp("README", "readme"), Ordering::Equal);
+    }
+
+    #[test]
+    fn shorter_prefix_comes_first() {
+        assert_eq!(natural_cmp("file", "file1"), Ordering::Less);
+        assert_eq!(natural_cmp("", ""), Ordering::Equal);
+    }
+
+    #[test]
+    fn sorts_by_key_then_name() {
+        let entry = |name: &str, r#type: &str| tree::Entry { name: name.to_string(), path: format!("/{}", name), r#type: r#type.to_string() };
+        let names = |key: SortKey, reverse: bool| -> Vec<String> {
+            let mut entries = vec![entry("b10.txt", "f"), entry("src", "d"), entry("b2.rs", "f"), entry("lib", "dl")];
+            sort_entries(&mut entries, &SortOrder { key, reverse }, &DiskBackend::default());
+            entries.into_iter().map(|e| e.name).collect()
+        };
+
+        assert_eq!(names(SortKey::Tree, false), vec!["b10.txt", "src", "b2.rs", "lib"]);
+        assert_eq!(names(SortKey::Name, false), vec!["b2.rs", "b10.txt", "lib", "src"]);
+        assert_eq!(names(SortKey::Type, false), vec!["lib", "src", "b2.rs", "b10.txt"]);
+        assert_eq!(names(SortKey::Extension, false), vec!["lib", "src", "b2.rs", "b10.txt"]);
+        assert_eq!(names(SortKey::Name, true), vec!["src", "lib", "b10.txt", "b2.rs"]);
+    }
+}
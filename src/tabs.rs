@@ -0,0 +1,31 @@
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+
+// Where a tab was left while another one is shown
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Tab {
+    pub curr_path: String,
+    pub file_curr: usize,
+    pub top_offset: i32,
+    #[serde(skip)]
+    pub select_start: Option<i32>,
+}
+
+impl Tab {
+    pub fn new(curr_path: &str) -> Tab {
+        Tab {
+            curr_path: curr_path.to_string(),
+            file_curr: 0,
+            top_offset: 0,
+            select_start: None,
+        }
+    }
+}
+
+// Name shown in the tab bar, the last part of the path
+pub fn label(curr_path: &str) -> String {
+    match Path::new(curr_path).file_name() {
+        Some(name) => name.to_string_lossy().to_string(),
+        None => String::from("/"),
+    }
+}
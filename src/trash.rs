@@ -0,0 +1,198 @@
+use std::env;
+use std::fs::*;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::date;
+use crate::disk;
+
+// A file in the trash as described by its `.trashinfo` file
+pub struct TrashItem {
+    pub name: String,
+    pub path: String,
+    pub deletion_date: String,
+}
+
+// $XDG_DATA_HOME/Trash, see https://specifications.freedesktop.org/trash-spec/latest/
+fn trash_dir() -> PathBuf {
+    match env::var("XDG_DATA_HOME") {
+        Ok(dir) if !dir.is_empty() => PathBuf::from(dir).join("Trash"),
+        _ => PathBuf::from(env::var("HOME").unwrap_or_default()).join(".local/share/Trash"),
+    }
+}
+
+fn info_path(name: &str) -> PathBuf {
+    trash_dir().join("info").join(format!("{}.trashinfo", name))
+}
+
+// Moves `path` into the trash and returns the name it was stored under
+pub fn trash(path: &Path) -> io::Result<String> {
+    create_dir_all(trash_dir().join("files"))?;
+    create_dir_all(trash_dir().join("info"))?;
+
+    let file_name = path.file_name().unwrap().to_str().unwrap();
+    let mut name = file_name.to_string();
+    let mut i = 1;
+
+    // The info file is created first and kept so another trasher cannot take the same name
+    let mut info = loop {
+        if symlink_metadata(trash_dir().join("files").join(&name)).is_err() {
+            match OpenOptions::new().write(true).create_new(true).open(info_path(&name)) {
+                Ok(info) => break info,
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {},
+                Err(err) => return Err(err),
+            }
+        }
+
+        i += 1;
+        name = format!("{}.{}", file_name, i);
+    };
+
+    if let Err(err) = info.write_all(trash_info(path).as_bytes()).and_then(|_| move_to_trash(path, &name)) {
+        remove_file(info_path(&name)).ok();
+        return Err(err);
+    }
+
+    Ok(name)
+}
+
+// Trashes `path` again under the name it had before, used to redo a deletion
+pub fn trash_as(path: &Path, name: &str) -> io::Result<()> {
+    write(info_path(name), trash_info(path))?;
+
+    if let Err(err) = move_to_trash(path, name) {
+        remove_file(info_path(name)).ok();
+        return Err(err);
+    }
+
+    Ok(())
+}
+
+fn trash_info(path: &Path) -> String {
+    format!("[Trash Info]\nPath={}\nDeletionDate={}\n", encode_path(&path.display().to_string()), deletion_date())
+}
+
+fn move_to_trash(path: &Path, name: &str) -> io::Result<()> {
+    disk::move_path(path, &trash_dir().join("files").join(name))
+}
+
+pub fn restore(name: &str, path: &Path) -> io::Result<()> {
+    if path.exists() {
+        return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("{} already exists", path.display())));
+    }
+
+    disk::move_path(&trash_dir().join("files").join(name), path)?;
+    remove_file(info_path(name))
+}
+
+pub fn purge(name: &str) -> io::Result<()> {
+    let file = trash_dir().join("files").join(name);
+    if file.exists() || file.is_symlink() {
+        disk::remove_path(&file)?;
+    }
+
+    remove_file(info_path(name))
+}
+
+pub fn list() -> io::Result<Vec<TrashItem>> {
+    let mut items: Vec<TrashItem> = Vec::new();
+    let info_dir = trash_dir().join("info");
+
+    if !info_dir.exists() {
+        return Ok(items);
+    }
+
+    for entry_res in read_dir(info_dir)? {
+        let entry = entry_res?;
+        let file_name = entry.file_name().to_str().unwrap_or("").to_string();
+
+        let name = match file_name.strip_suffix(".trashinfo") {
+            Some(name) => name.to_string(),
+            None => continue,
+        };
+
+        let mut item = TrashItem {
+            name,
+            path: String::new(),
+            deletion_date: String::new(),
+        };
+
+        for line in read_to_string(entry.path())?.lines() {
+            if let Some(path) = line.strip_prefix("Path=") {
+                item.path = decode_path(path);
+            } else if let Some(date) = line.strip_prefix("DeletionDate=") {
+                item.deletion_date = date.to_string();
+            }
+        }
+
+        items.push(item);
+    }
+
+    items.sort_by(|a, b| b.deletion_date.cmp(&a.deletion_date));
+
+    Ok(items)
+}
+
+// YYYY-MM-DDThh:mm:ss in local time, the spec leaves out the time zone
+fn deletion_date() -> String {
+    let (year, month, day, hour, minute, second) = date::local(SystemTime::now());
+
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}", year, month, day, hour, minute, second)
+}
+
+fn encode_path(path: &str) -> String {
+    let mut encoded = String::new();
+
+    for b in path.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' => encoded.push(b as char),
+            _ => encoded.push_str(&format!("%{:02X}", b)),
+        }
+    }
+
+    encoded
+}
+
+fn decode_path(path: &str) -> String {
+    let bytes = path.as_bytes();
+    let mut decoded: Vec<u8> = Vec::new();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            if let Some(b) = std::str::from_utf8(&bytes[i + 1..i + 3]).ok().and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                decoded.push(b);
+                i += 3;
+                continue;
+            }
+        }
+
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_escaped_bytes() {
+        assert_eq!(decode_path("/home/a%20b/%C3%A9.txt"), "/home/a b/é.txt");
+    }
+
+    #[test]
+    fn keeps_invalid_escapes() {
+        assert_eq!(decode_path("/a%zz/b%4"), "/a%zz/b%4");
+        assert_eq!(decode_path("100%"), "100%");
+    }
+
+    #[test]
+    fn decodes_what_it_encodes() {
+        let path = "/tmp/some dir/naïve #1%.txt";
+        assert_eq!(decode_path(&encode_path(path)), path);
+    }
+}
@@ -1,6 +1,13 @@
+use std::cmp::min;
+use std::collections::HashMap;
+use std::fmt;
 use std::fs;
+use std::path::Path;
 use serde::{Deserialize, Serialize};
 
+// Links followed while resolving a single path before giving up
+const MAX_LINK_DEPTH: usize = 32;
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Entry {
     pub name: String,
@@ -16,15 +23,257 @@ pub struct Link {
     pub r#type: String,
 }
 
-#[derive(Serialize, Deserialize)]
+// Records removed from the tree when `path` was moved to the trash as `name`
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Trashed {
+    pub name: String,
+    pub path: String,
+    pub entries: Vec<Entry>,
+    pub links: Vec<Link>,
+}
+
+// Records directly inside a folder, in the order they have in `root` and `_links`
+#[derive(Default, Clone)]
+pub struct Children {
+    pub entries: Vec<Entry>,
+    pub links: Vec<Link>,
+}
+
+// Lookup tables keyed by path so resolving and listing don't scan `root` and `_links`
+#[derive(Default, Clone)]
+pub struct PathIndex {
+    entries: HashMap<String, usize>,
+    links: HashMap<String, String>,
+    children: HashMap<String, Children>,
+}
+
+pub fn file_name(path: &str) -> String {
+    Path::new(path).file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default()
+}
+
+pub fn parent_path(path: &str) -> &str {
+    match path.rfind('/') {
+        Some(0) | None => "/",
+        Some(i) => &path[..i],
+    }
+}
+
+// `position` counts the records of the same folder before it, past the end appends
+impl PathIndex {
+    pub fn insert_entry(&mut self, entry: &Entry, position: usize) {
+        *self.entries.entry(entry.path.clone()).or_insert(0) += 1;
+
+        let siblings = &mut self.children.entry(parent_path(&entry.path).to_string()).or_default().entries;
+        siblings.insert(min(position, siblings.len()), entry.clone());
+    }
+
+    pub fn remove_entry(&mut self, path: &str) {
+        if let Some(count) = self.entries.get_mut(path) {
+            *count -= 1;
+            if *count == 0 {
+                self.entries.remove(path);
+            }
+        }
+
+        if let Some(children) = self.children.get_mut(parent_path(path)) {
+            if let Some(i) = children.entries.iter().position(|e| e.path == path) {
+                children.entries.remove(i);
+            }
+        }
+    }
+
+    pub fn insert_link(&mut self, link: &Link, position: usize) {
+        self.links.insert(link.path.clone(), link.link_path.clone());
+
+        let siblings = &mut self.children.entry(parent_path(&link.path).to_string()).or_default().links;
+        siblings.insert(min(position, siblings.len()), link.clone());
+    }
+
+    pub fn remove_link(&mut self, path: &str) {
+        self.links.remove(path);
+
+        if let Some(children) = self.children.get_mut(parent_path(path)) {
+            if let Some(i) = children.links.iter().position(|l| l.path == path) {
+                children.links.remove(i);
+            }
+        }
+    }
+
+    pub fn has_entry(&self, path: &str) -> bool {
+        self.entries.contains_key(path)
+    }
+
+    pub fn entry(&self, path: &str) -> Option<&Entry> {
+        self.children.get(parent_path(path))?.entries.iter().find(|e| e.path == path)
+    }
+
+    pub fn link_target(&self, path: &str) -> Option<&String> {
+        self.links.get(path)
+    }
+
+    pub fn children(&self, path: &str) -> Option<&Children> {
+        self.children.get(path)
+    }
+}
+
+#[derive(Debug)]
+pub enum ResolveError {
+    NotFound(String),
+    Cycle(String),
+    TooDeep(String),
+}
+
+impl fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ResolveError::NotFound(path) => write!(f, "Path not found: {}", path),
+            ResolveError::Cycle(path) => write!(f, "Link cycle at: {}", path),
+            ResolveError::TooDeep(path) => write!(f, "Too many levels of links at: {}", path),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
 pub struct Tree {
     pub root: Vec<Entry>,
     pub _links: Vec<Link>,
+    #[serde(default)]
+    pub _trash: Vec<Trashed>,
+    #[serde(skip)]
+    pub index: PathIndex,
+}
+
+impl Tree {
+    pub fn build_index(&mut self) {
+        self.index = PathIndex::default();
+
+        for e in self.root.iter() {
+            self.index.insert_entry(e, usize::MAX);
+        }
+
+        for l in self._links.iter() {
+            self.index.insert_link(l, usize::MAX);
+        }
+    }
+
+    // Maps a path that may go through links, or links to other links, to the real path of the entry
+    pub fn resolve(&self, path: &str) -> Result<String, ResolveError> {
+        self.resolve_links(path, &mut vec![])
+    }
+
+    // `followed` holds the chain of links being resolved right now, a path can pass the same link twice
+    fn resolve_links(&self, path: &str, followed: &mut Vec<String>) -> Result<String, ResolveError> {
+        if path == "/" || self.index.has_entry(path) {
+            return Ok(path.to_string());
+        }
+
+        let mut real_path = String::new();
+
+        for name in path.split('/').skip(1) {
+            let new_path = format!("{}/{}", real_path, name);
+
+            if self.index.has_entry(&new_path) {
+                real_path = new_path;
+            } else if let Some(link_path) = self.index.link_target(&new_path) {
+                if followed.contains(&new_path) {
+                    return Err(ResolveError::Cycle(new_path));
+                } else if followed.len() >= MAX_LINK_DEPTH {
+                    return Err(ResolveError::TooDeep(new_path));
+                }
+
+                followed.push(new_path);
+                real_path = self.resolve_links(link_path, followed)?;
+                followed.pop();
+            } else {
+                return Err(ResolveError::NotFound(new_path));
+            }
+        }
+
+        Ok(real_path)
+    }
 }
 
-pub fn parse_tree() -> Tree {
-    let data = fs::read_to_string("tree.json").unwrap();
-    let t: Tree = serde_json::from_str(&data).unwrap();
+pub fn parse_tree(path: &Path) -> Result<Tree, String> {
+    let data = fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+    let mut t: Tree = serde_json::from_str(&data).map_err(|err| format!("{}: {}", path.display(), err))?;
+    t.build_index();
+
+    Ok(t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree(entries: &[&str], links: &[(&str, &str)]) -> Tree {
+        let mut tree = Tree {
+            root: entries.iter().map(|path| Entry { name: path.rsplit('/').next().unwrap().to_string(), path: path.to_string(), r#type: String::from("d") }).collect(),
+            _links: links.iter().map(|(path, link_path)| Link {
+                name: path.rsplit('/').next().unwrap().to_string(),
+                path: path.to_string(),
+                link_path: link_path.to_string(),
+                r#type: String::from("d"),
+            }).collect(),
+            ..Default::default()
+        };
+        tree.build_index();
+        tree
+    }
+
+    #[test]
+    fn resolves_entries_and_links() {
+        let tree = tree(&["/x", "/x/y"], &[("/a", "/x")]);
+
+        assert_eq!(tree.resolve("/").unwrap(), "/");
+        assert_eq!(tree.resolve("/x/y").unwrap(), "/x/y");
+        assert_eq!(tree.resolve("/a/y").unwrap(), "/x/y");
+        assert!(matches!(tree.resolve("/a/z"), Err(ResolveError::NotFound(path)) if path == "/x/z"));
+    }
+
+    #[test]
+    fn follows_links_to_links() {
+        let tree = tree(&["/x", "/x/y"], &[("/a", "/b"), ("/b", "/c"), ("/c", "/x")]);
+        assert_eq!(tree.resolve("/a/y").unwrap(), "/x/y");
+    }
+
+    #[test]
+    fn a_path_can_pass_the_same_link_twice() {
+        let tree = tree(&["/x", "/x/y"], &[("/a", "/x"), ("/x/b", "/a/y")]);
+        assert_eq!(tree.resolve("/a/b").unwrap(), "/x/y");
+    }
+
+    #[test]
+    fn reports_cycles() {
+        let tree = tree(&[], &[("/a", "/b"), ("/b", "/a")]);
+        assert!(matches!(tree.resolve("/a"), Err(ResolveError::Cycle(path)) if path == "/a"));
+    }
+
+    #[test]
+    fn gives_up_on_long_chains() {
+        let links: Vec<(String, String)> = (0..=MAX_LINK_DEPTH).map(|i| (format!("/l{}", i), format!("/l{}", i + 1))).collect();
+        let links: Vec<(&str, &str)> = links.iter().map(|(path, link_path)| (path.as_str(), link_path.as_str())).collect();
+        let tree = tree(&[], &links);
+
+        assert!(matches!(tree.resolve("/l0"), Err(ResolveError::TooDeep(_))));
+    }
+
+    #[test]
+    fn indexes_children_by_folder() {
+        let mut tree = tree(&["/x", "/x/y", "/x/z", "/w"], &[("/x/a", "/w")]);
+        let names = |tree: &Tree, path: &str| -> Vec<String> {
+            let children = tree.index.children(path).unwrap();
+            children.links.iter().map(|l| l.name.clone()).chain(children.entries.iter().map(|e| e.name.clone())).collect()
+        };
+
+        assert_eq!(names(&tree, "/"), vec!["x", "w"]);
+        assert_eq!(names(&tree, "/x"), vec!["a", "y", "z"]);
+        assert!(tree.index.children("/w").is_none());
+        assert_eq!(tree.index.entry("/x/z").unwrap().name, "z");
 
-    t
+        tree.index.remove_entry("/x/y");
+        tree.index.remove_link("/x/a");
+        assert_eq!(names(&tree, "/x"), vec!["z"]);
+        assert!(!tree.index.has_entry("/x/y"));
+        assert!(tree.index.link_target("/x/a").is_none());
+    }
 }
@@ -0,0 +1,591 @@
+use std::cmp::min;
+use std::fs::{self, File, Metadata};
+use std::io;
+use std::path::{Path, PathBuf};
+use serde_json::to_writer;
+
+use crate::archive;
+use crate::backend::{Backend, TreeStore};
+use crate::disk;
+use crate::find;
+use crate::journal::{DiskOp, Record, TreeOp};
+use crate::reconcile;
+use crate::trash;
+use crate::tree;
+
+// Lists what tree.json knows about. Files and directories are changed on disk as
+// well, links only exist in the tree.
+pub struct TreeBackend {
+    tree: tree::Tree,
+    path: PathBuf,
+    ops: Vec<DiskOp>,
+    tree_ops: Vec<TreeOp>,
+}
+
+impl TreeBackend {
+    pub fn new(path: PathBuf) -> Result<TreeBackend, String> {
+        Ok(TreeBackend {
+            tree: tree::parse_tree(&path)?,
+            path,
+            ops: vec![],
+            tree_ops: vec![],
+        })
+    }
+
+    // Links only exist in the tree, there is nothing on disk to change for them
+    fn is_link(&self, path: &str) -> bool {
+        self.tree.index.link_target(path).is_some()
+    }
+
+    fn entries_in(&self, path: &str) -> Vec<tree::Entry> {
+        let children = match self.tree.index.children(path) {
+            Some(children) => children,
+            None => return vec![],
+        };
+        let mut entries: Vec<tree::Entry> = vec![];
+
+        for e in children.links.iter() {
+            // Broken links are listed as "bl" and cyclic ones as "cl" so they can't be entered
+            let r#type = match self.tree.resolve(&e.path) {
+                Ok(_) => format!("{}l", e.r#type),
+                Err(tree::ResolveError::NotFound(_)) => String::from("bl"),
+                Err(_) => String::from("cl"),
+            };
+
+            entries.push(tree::Entry {
+                name: e.name.clone(),
+                path: e.path.clone(),
+                r#type,
+            });
+        }
+
+        entries.extend(children.entries.iter().cloned());
+
+        entries
+    }
+
+    // Changes the tree and keeps the op for the journal
+    fn push_op(&mut self, op: TreeOp) {
+        self.apply(&op);
+        self.tree_ops.push(op);
+    }
+
+    fn add_entry(&mut self, entry: tree::Entry) {
+        self.push_op(TreeOp::Add { index: 0, record: Record::Entry(entry) });
+    }
+
+    fn add_link(&mut self, link: tree::Link) {
+        self.push_op(TreeOp::Add { index: 0, record: Record::Link(link) });
+    }
+
+    fn delete_entry(&mut self, entry_path: &str) -> (Vec<tree::Entry>, Vec<tree::Link>) {
+        let mut found_flag = false;
+        let mut removed_entries: Vec<tree::Entry> = vec![];
+        let mut removed_links: Vec<tree::Link> = vec![];
+
+        let mut i = 0;
+
+        while i < self.tree._links.len() {
+            let link = &self.tree._links[i];
+            let is_link = link.path == entry_path;
+
+            if link.link_path.starts_with(&format!("{}/", entry_path)) || link.link_path == entry_path || is_link {
+                let link = link.clone();
+                self.push_op(TreeOp::Remove { index: i, record: Record::Link(link.clone()) });
+                removed_links.push(link);
+                found_flag |= is_link;
+            } else {
+                i += 1;
+            }
+        }
+
+        if !found_flag {
+            let mut i = 0;
+
+            while i < self.tree.root.len() {
+                let entry = &self.tree.root[i];
+
+                if entry.path == entry_path || entry.path.starts_with(&format!("{}/", entry_path)) {
+                    let entry = entry.clone();
+                    self.push_op(TreeOp::Remove { index: i, record: Record::Entry(entry.clone()) });
+                    removed_entries.push(entry);
+                } else {
+                    i += 1;
+                }
+            }
+        }
+
+        (removed_entries, removed_links)
+    }
+
+    // Forgets `path` and everything below it once it is off disk. What went to the
+    // trash as `trash_name` is kept so `restore` can bring it back.
+    fn forget(&mut self, path: &str, trash_name: Option<String>) {
+        let (entries, links) = self.delete_entry(path);
+
+        if let Some(name) = trash_name {
+            let trashed = tree::Trashed { name, path: path.to_string(), entries, links };
+            self.push_op(TreeOp::Add { index: self.tree._trash.len(), record: Record::Trashed(trashed) });
+        }
+    }
+
+    fn insert_record(&mut self, index: usize, record: Record) {
+        // Where the record goes among the ones of the same folder
+        fn position<'a>(before: impl Iterator<Item = &'a String>, path: &str) -> usize {
+            let parent = tree::parent_path(path);
+            before.filter(|p| tree::parent_path(p) == parent).count()
+        }
+
+        match record {
+            Record::Entry(e) => {
+                let index = min(index, self.tree.root.len());
+                self.tree.index.insert_entry(&e, position(self.tree.root[..index].iter().map(|x| &x.path), &e.path));
+                self.tree.root.insert(index, e);
+            },
+            Record::Link(l) => {
+                let index = min(index, self.tree._links.len());
+                self.tree.index.insert_link(&l, position(self.tree._links[..index].iter().map(|x| &x.path), &l.path));
+                self.tree._links.insert(index, l);
+            },
+            Record::Trashed(t) => self.tree._trash.insert(min(index, self.tree._trash.len()), t),
+        }
+    }
+
+    // The record is looked for at `index` first, the tree may have been reloaded since
+    fn remove_record(&mut self, index: usize, record: &Record) {
+        fn take_at<T>(list: &mut Vec<T>, index: usize, matches: impl Fn(&T) -> bool) -> Option<T> {
+            let i = if list.get(index).map(&matches).unwrap_or(false) { Some(index) } else { list.iter().position(matches) };
+            i.map(|i| list.remove(i))
+        }
+
+        match record {
+            Record::Entry(e) => {
+                if take_at(&mut self.tree.root, index, |x| x.path == e.path).is_some() {
+                    self.tree.index.remove_entry(&e.path);
+                }
+            },
+            Record::Link(l) => {
+                if take_at(&mut self.tree._links, index, |x| x.path == l.path).is_some() {
+                    self.tree.index.remove_link(&l.path);
+                }
+            },
+            Record::Trashed(t) => { take_at(&mut self.tree._trash, index, |x| x.name == t.name); },
+        }
+    }
+
+    fn move_records(&mut self, old_path: &str, new_path: &str) {
+        fn replace_prefix(path: &mut String, old_path: &str, new_path: &str) -> bool {
+            if path == old_path {
+                *path = new_path.to_string();
+                true
+            } else {
+                if let Some(rest) = path.strip_prefix(&format!("{}/", old_path)) {
+                    *path = format!("{}/{}", new_path, rest);
+                }
+                false
+            }
+        }
+
+        let new_name = tree::file_name(new_path);
+
+        for e in self.tree.root.iter_mut() {
+            if replace_prefix(&mut e.path, old_path, new_path) {
+                e.name = new_name.clone();
+            }
+        }
+
+        for l in self.tree._links.iter_mut() {
+            if replace_prefix(&mut l.path, old_path, new_path) {
+                l.name = new_name.clone();
+            }
+            replace_prefix(&mut l.link_path, old_path, new_path);
+        }
+
+        // Whole folders change parents, it is simpler to index everything again
+        self.tree.build_index();
+    }
+
+    fn copy_entry(&mut self, src_path: &str, dest_path: &str) {
+        fn with_prefix(path: &str, src_path: &str, dest_path: &str) -> Option<String> {
+            if path == src_path {
+                Some(dest_path.to_string())
+            } else {
+                path.strip_prefix(&format!("{}/", src_path)).map(|rest| format!("{}/{}", dest_path, rest))
+            }
+        }
+
+        let dest_name = tree::file_name(dest_path);
+
+        let mut new_links: Vec<tree::Link> = vec![];
+        for l in self.tree._links.iter() {
+            if let Some(path) = with_prefix(&l.path, src_path, dest_path) {
+                new_links.push(tree::Link {
+                    name: if l.path == src_path { dest_name.clone() } else { l.name.clone() },
+                    path,
+                    link_path: with_prefix(&l.link_path, src_path, dest_path).unwrap_or_else(|| l.link_path.clone()),
+                    r#type: l.r#type.clone(),
+                });
+            }
+        }
+
+        let mut new_entries: Vec<tree::Entry> = vec![];
+        for e in self.tree.root.iter() {
+            if let Some(path) = with_prefix(&e.path, src_path, dest_path) {
+                new_entries.push(tree::Entry {
+                    name: if e.path == src_path { dest_name.clone() } else { e.name.clone() },
+                    path,
+                    r#type: e.r#type.clone(),
+                });
+            }
+        }
+
+        for l in new_links.into_iter().rev() {
+            self.add_link(l);
+        }
+
+        for e in new_entries.into_iter().rev() {
+            self.add_entry(e);
+        }
+    }
+}
+
+impl Backend for TreeBackend {
+    fn list(&self, path: &str) -> Result<Vec<tree::Entry>, String> {
+        if let Some((archive_path, inner)) = archive::split(path, |p| self.resolve(p).ok()) {
+            return archive::list(&archive_path, &inner, path).map_err(|err| err.to_string());
+        }
+
+        let real_path = self.resolve(path).map_err(|err| err.to_string())?;
+        Ok(self.entries_in(&real_path))
+    }
+
+    fn stat(&self, path: &str) -> io::Result<Metadata> {
+        fs::metadata(self.resolve(path).unwrap_or_else(|_| path.to_string()))
+    }
+
+    fn resolve(&self, path: &str) -> Result<String, tree::ResolveError> {
+        self.tree.resolve(path)
+    }
+
+    fn link_target(&self, path: &str) -> Option<String> {
+        self.tree.index.link_target(path).cloned().or_else(|| {
+            let real_path = self.resolve(path).ok()?;
+            fs::read_link(real_path).ok().map(|target| target.display().to_string())
+        })
+    }
+
+    fn exists(&self, path: &str) -> bool {
+        self.tree.index.link_target(path).is_some() || Path::new(path).exists()
+    }
+
+    fn create_file(&mut self, path: &str) -> Result<tree::Entry, String> {
+        if Path::new(path).exists() {
+            return Err(String::from("File already exists"));
+        }
+
+        File::create(path).map_err(|err| err.to_string())?;
+        self.ops.push(DiskOp::Create { path: path.to_string() });
+
+        let entry = tree::Entry { name: tree::file_name(path), path: path.to_string(), r#type: String::from("f") };
+        self.add_entry(entry.clone());
+        Ok(entry)
+    }
+
+    fn create_dir(&mut self, path: &str) -> Result<tree::Entry, String> {
+        fs::create_dir(path).map_err(|err| err.to_string())?;
+        self.ops.push(DiskOp::Create { path: path.to_string() });
+
+        let entry = tree::Entry { name: tree::file_name(path), path: path.to_string(), r#type: String::from("d") };
+        self.add_entry(entry.clone());
+        Ok(entry)
+    }
+
+    fn link(&mut self, target: &str, path: &str) -> Result<(), String> {
+        let r#type = match self.tree.index.entry(target) {
+            Some(e) => e.r#type.clone(),
+            None => return Err(format!("{} is not in the tree", target)),
+        };
+
+        if self.is_link(path) {
+            return Err(format!("{} already exists", tree::file_name(path)));
+        }
+
+        self.add_link(tree::Link {
+            name: tree::file_name(path),
+            path: path.to_string(),
+            link_path: target.to_string(),
+            r#type,
+        });
+        Ok(())
+    }
+
+    fn rename(&mut self, from: &str, to: &str) -> Result<(), String> {
+        if !self.is_link(from) {
+            if Path::new(to).exists() {
+                return Err(String::from("File already exists"));
+            }
+            disk::move_path(Path::new(from), Path::new(to)).map_err(|err| err.to_string())?;
+            self.ops.push(DiskOp::Move { from: from.to_string(), to: to.to_string() });
+        }
+
+        self.push_op(TreeOp::Rename { from: from.to_string(), to: to.to_string() });
+        Ok(())
+    }
+
+    fn copy(&mut self, from: &str, to: &str) -> Result<(), String> {
+        if !self.is_link(from) {
+            disk::copy_recursive(Path::new(from), Path::new(to)).map_err(|err| err.to_string())?;
+            self.ops.push(DiskOp::Create { path: to.to_string() });
+        }
+
+        self.copy_entry(from, to);
+        Ok(())
+    }
+
+    fn trash(&mut self, path: &str) -> Result<(), String> {
+        if self.is_link(path) {
+            self.forget(path, None);
+            return Ok(());
+        }
+
+        let name = trash::trash(Path::new(path)).map_err(|err| err.to_string())?;
+        self.ops.push(DiskOp::Trash { path: path.to_string(), name: name.clone() });
+        self.forget(path, Some(name));
+        Ok(())
+    }
+
+    fn delete(&mut self, path: &str, staged: &str) -> Result<(), String> {
+        if !self.is_link(path) {
+            disk::move_path(Path::new(path), Path::new(staged)).map_err(|err| err.to_string())?;
+            self.ops.push(DiskOp::Move { from: path.to_string(), to: staged.to_string() });
+        }
+
+        self.forget(path, None);
+        Ok(())
+    }
+
+    fn restore(&mut self, path: &str, trash_name: &str) -> Result<(), String> {
+        trash::restore(trash_name, Path::new(path)).map_err(|err| err.to_string())?;
+        self.ops.push(DiskOp::Restore { path: path.to_string(), name: trash_name.to_string() });
+
+        match self.tree._trash.iter().position(|t| t.name == trash_name) {
+            Some(i) => {
+                let trashed = self.tree._trash[i].clone();
+                self.push_op(TreeOp::Remove { index: i, record: Record::Trashed(trashed.clone()) });
+                for l in trashed.links.into_iter().rev() {
+                    self.add_link(l);
+                }
+                for e in trashed.entries.into_iter().rev() {
+                    self.add_entry(e);
+                }
+            },
+            // Trashed outside of the explorer, there are no records to bring back
+            None => self.add_entry(tree::Entry {
+                name: tree::file_name(path),
+                path: path.to_string(),
+                r#type: String::from(if Path::new(path).is_dir() { "d" } else { "f" }),
+            }),
+        }
+
+        Ok(())
+    }
+
+    fn purge(&mut self, trash_name: &str) -> Result<(), String> {
+        trash::purge(trash_name).map_err(|err| err.to_string())?;
+        self.tree._trash.retain(|t| t.name != trash_name);
+        Ok(())
+    }
+
+    fn trash_items(&self) -> Result<Vec<trash::TrashItem>, String> {
+        trash::list().map_err(|err| err.to_string())
+    }
+
+    fn compress(&mut self, path: &str, format: archive::ArchiveFormat, sources: &[(String, PathBuf)], progress: &mut dyn FnMut(u64, u64)) -> Result<(), String> {
+        if Path::new(path).exists() {
+            return Err(String::from("File already exists"));
+        }
+
+        if let Err(err) = archive::create(Path::new(path), format, sources, progress) {
+            fs::remove_file(path).ok();
+            return Err(err.to_string());
+        }
+
+        self.ops.push(DiskOp::Create { path: path.to_string() });
+        self.add_entry(tree::Entry { name: tree::file_name(path), path: path.to_string(), r#type: String::from("f") });
+        Ok(())
+    }
+
+    fn take_ops(&mut self) -> Vec<DiskOp> {
+        std::mem::take(&mut self.ops)
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let file = File::create(&self.path).map_err(|err| err.to_string())?;
+        to_writer(&file, &self.tree).map_err(|err| err.to_string())
+    }
+
+    fn tree(&mut self) -> Option<&mut dyn TreeStore> {
+        Some(self)
+    }
+}
+
+impl TreeStore for TreeBackend {
+    fn find(&self, query: &str, mode: &find::FindMode) -> Result<Vec<tree::Entry>, String> {
+        find::find(&self.tree, query, mode).map_err(|err| err.to_string())
+    }
+
+    fn sync(&self, dir: &str) -> Result<Vec<reconcile::Diff>, String> {
+        reconcile::reconcile(&self.tree, dir).map_err(|err| err.to_string())
+    }
+
+    fn apply_diff(&mut self, diff: reconcile::Diff) {
+        match diff {
+            reconcile::Diff::DiskOnly(entry) => self.add_entry(entry),
+            reconcile::Diff::TreeOnly(entry) => { self.delete_entry(&entry.path); },
+        }
+    }
+
+    fn reload(&mut self) -> Result<(), String> {
+        self.tree = tree::parse_tree(&self.path)?;
+        self.tree_ops.clear();
+        Ok(())
+    }
+
+    fn take_tree_ops(&mut self) -> Vec<TreeOp> {
+        std::mem::take(&mut self.tree_ops)
+    }
+
+    fn apply(&mut self, op: &TreeOp) {
+        match op {
+            TreeOp::Add { index, record } => self.insert_record(*index, record.clone()),
+            TreeOp::Remove { index, record } => self.remove_record(*index, record),
+            TreeOp::Rename { from, to } => self.move_records(from, to),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn backend(entries: &[(&str, &str)], links: &[(&str, &str, &str)]) -> TreeBackend {
+        let mut tree = tree::Tree {
+            root: entries.iter().map(|(path, r#type)| tree::Entry { name: tree::file_name(path), path: path.to_string(), r#type: r#type.to_string() }).collect(),
+            _links: links.iter().map(|(path, link_path, r#type)| tree::Link {
+                name: tree::file_name(path),
+                path: path.to_string(),
+                link_path: link_path.to_string(),
+                r#type: r#type.to_string(),
+            }).collect(),
+            ..Default::default()
+        };
+        tree.build_index();
+
+        TreeBackend { tree, path: PathBuf::new(), ops: vec![], tree_ops: vec![] }
+    }
+
+    fn entries(backend: &TreeBackend) -> Vec<(String, String)> {
+        backend.tree.root.iter().map(|e| (e.name.clone(), e.path.clone())).collect()
+    }
+
+    fn links(backend: &TreeBackend) -> Vec<(String, String, String)> {
+        backend.tree._links.iter().map(|l| (l.name.clone(), l.path.clone(), l.link_path.clone())).collect()
+    }
+
+    fn listed(backend: &TreeBackend, path: &str) -> Vec<String> {
+        backend.list(path).unwrap().into_iter().map(|e| format!("{} {}", e.r#type, e.name)).collect()
+    }
+
+    fn undo(backend: &mut TreeBackend) {
+        for op in backend.take_tree_ops().iter().rev() {
+            backend.apply(&op.inverse());
+        }
+    }
+
+    fn owned<const N: usize>(items: [(&str, &str); N]) -> Vec<(String, String)> {
+        items.iter().map(|(a, b)| (a.to_string(), b.to_string())).collect()
+    }
+
+    fn owned_links<const N: usize>(items: [(&str, &str, &str); N]) -> Vec<(String, String, String)> {
+        items.iter().map(|(a, b, c)| (a.to_string(), b.to_string(), c.to_string())).collect()
+    }
+
+    #[test]
+    fn renames_everything_below_a_folder() {
+        let mut backend = backend(&[("/d", "d"), ("/d/f", "f"), ("/dx", "d"), ("/o", "d")], &[("/d/l", "/o", "d"), ("/k", "/d/f", "f")]);
+
+        backend.push_op(TreeOp::Rename { from: String::from("/d"), to: String::from("/e") });
+
+        assert_eq!(entries(&backend), owned([("e", "/e"), ("f", "/e/f"), ("dx", "/dx"), ("o", "/o")]));
+        assert_eq!(links(&backend), owned_links([("l", "/e/l", "/o"), ("k", "/k", "/e/f")]));
+        assert_eq!(backend.resolve("/k").unwrap(), "/e/f");
+        assert_eq!(listed(&backend, "/e"), vec!["dl l", "f f"]);
+        assert!(backend.list("/d").is_err());
+
+        backend.push_op(TreeOp::Rename { from: String::from("/e/f"), to: String::from("/e/g") });
+        assert_eq!(links(&backend)[1], (String::from("k"), String::from("/k"), String::from("/e/g")));
+
+        undo(&mut backend);
+        assert_eq!(entries(&backend), owned([("d", "/d"), ("f", "/d/f"), ("dx", "/dx"), ("o", "/o")]));
+        assert_eq!(links(&backend), owned_links([("l", "/d/l", "/o"), ("k", "/k", "/d/f")]));
+        assert_eq!(listed(&backend, "/"), vec!["fl k", "d d", "d dx", "d o"]);
+    }
+
+    #[test]
+    fn copies_links_as_links() {
+        let mut backend = backend(&[("/d", "d"), ("/d/f", "f"), ("/o", "d")], &[("/d/in", "/d/f", "f"), ("/d/out", "/o", "d"), ("/k", "/d", "d")]);
+
+        backend.copy_entry("/d", "/c");
+        assert_eq!(listed(&backend, "/c"), vec!["fl in", "dl out", "f f"]);
+        assert_eq!(backend.link_target("/c/in").unwrap(), "/c/f");
+        assert_eq!(backend.link_target("/c/out").unwrap(), "/o");
+
+        // Copying a link copies the link, not what it points to
+        backend.copy_entry("/k", "/k2");
+        assert_eq!(backend.link_target("/k2").unwrap(), "/d");
+        assert_eq!(listed(&backend, "/"), vec!["dl k2", "dl k", "d c", "d d", "d o"]);
+
+        undo(&mut backend);
+        assert_eq!(entries(&backend), owned([("d", "/d"), ("f", "/d/f"), ("o", "/o")]));
+        assert_eq!(links(&backend), owned_links([("in", "/d/in", "/d/f"), ("out", "/d/out", "/o"), ("k", "/k", "/d")]));
+    }
+
+    #[test]
+    fn restores_what_was_trashed() {
+        let dir = std::env::temp_dir().join(format!("tree-backend-test-{}", std::process::id()));
+        fs::create_dir_all(dir.join("d")).unwrap();
+        fs::write(dir.join("d/f"), "").unwrap();
+        std::env::set_var("XDG_DATA_HOME", dir.join("share"));
+
+        let path = |name: &str| format!("{}{}", dir.display(), name);
+        let folders: Vec<String> = dir.ancestors().filter(|p| p.parent().is_some()).map(|p| p.display().to_string()).collect();
+        let mut records: Vec<(&str, &str)> = folders.iter().rev().map(|p| (p.as_str(), "d")).collect();
+        let (o, d, f) = (path("/o"), path("/d"), path("/d/f"));
+        records.extend([(o.as_str(), "d"), (d.as_str(), "d"), (f.as_str(), "f")]);
+
+        let mut backend = backend(&records, &[(&path("/l"), &f, "f"), (&path("/o/k"), &o, "d")]);
+        let before = (entries(&backend), links(&backend));
+
+        backend.trash(&path("/d")).unwrap();
+        assert!(!dir.join("d").exists());
+        assert_eq!(listed(&backend, &path("")), vec!["d o"]);
+        assert_eq!(backend.tree._trash.len(), 1);
+
+        let name = backend.tree._trash[0].name.clone();
+        backend.restore(&path("/d"), &name).unwrap();
+        assert!(dir.join("d/f").exists());
+        assert!(backend.tree._trash.is_empty());
+        assert_eq!(listed(&backend, &path("")), vec!["fl l", "d d", "d o"]);
+        assert_eq!(listed(&backend, &path("/d")), vec!["f f"]);
+
+        // Undoing both leaves the tree as it was
+        undo(&mut backend);
+        let mut after = (entries(&backend), links(&backend));
+        after.0.sort();
+        let mut before = before;
+        before.0.sort();
+        assert_eq!(after, before);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}